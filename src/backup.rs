@@ -1,7 +1,19 @@
 use crate::*;
-use std::io::Read;
 use flate2::Compression;
 use flate2::write::GzEncoder;
+use journal::Journal;
+use log::{debug, error, info, warn};
+use manifest::{Device, Manifest, ManifestEntry};
+use nix::errno::Errno;
+use output::{Event, emit};
+use progress::Progress;
+use report::Report;
+use std::collections::{BTreeMap, HashMap};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::PermissionsExt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use walk::{Entry, Kind, Walk};
 
 #[derive(Parser)]
 pub struct BackupArgs {
@@ -10,259 +22,1063 @@ pub struct BackupArgs {
     config_path: Option<PathBuf>,
 
     /// Backup files in scheduled intervals
-    #[arg(short, long)]
+    #[arg(short, long, value_parser = clap::value_parser!(u64).range(1..))]
     interval: Option<u64>,
 
     /// Backup files in real-time when changes are detected
     #[arg(short, long)]
     realtime: bool,
+
+    /// Do not show a progress line
+    #[arg(long)]
+    no_progress: bool,
+
+    /// Continue an interrupted backup instead of starting over
+    #[arg(long, conflicts_with_all = ["interval", "realtime"])]
+    resume: bool,
 }
 
-fn check_file_properties(
-    root_path: &Path,
-    file_path: &Path,
-    file_config: &config::FileConfig,
-) -> bool {
-    let metadata = match fs::symlink_metadata(&file_path) {
-        Ok(metadata) => metadata,
-        Err(_) => {
-            eprintln!("Failed to get metadata for {:?}", &file_path);
-            return false;
-        }
-    };
+/// Prefix of the temporary files that copies and archives are written to
+/// before being renamed into place.
+const TEMP_PREFIX: &str = ".babycancer-tmp-";
+
+/// Number of temporary names handed out by this process.
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Temporary name for `path`, in the same directory so that renaming it into
+/// place is atomic. The name does not depend on the length of the file name,
+/// which may already be as long as the file system allows.
+fn temp_path(path: &Path) -> PathBuf {
+    let count = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+    path.with_file_name(format!("{}{}-{}", TEMP_PREFIX, std::process::id(), count))
+}
+
+/// Write `contents` to `path` through a temporary file that is synced and
+/// renamed into place, so that a crash never leaves `path` truncated.
+pub fn write_file(path: &Path, contents: &[u8]) -> Result<(), Error> {
+    let temp_path = temp_path(path);
+    let result = File::create(&temp_path)
+        .and_then(|mut file| {
+            file.write_all(contents)?;
+            file.sync_all()
+        })
+        .and_then(|()| fs::rename(&temp_path, path));
+    if let Err(e) = result {
+        fs::remove_file(&temp_path).ok();
+        return Err(e).at(path);
+    }
+    Ok(())
+}
 
-    if let Some(ref config_path) = file_config.file_path {
-        if !(file_path).starts_with(root_path.join(config_path)) {
-            return false;
+/// Remove temporary files left in `dir_path` by an interrupted run.
+fn remove_temp_files(dir_path: &Path) -> Result<usize, Error> {
+    let mut removed = 0;
+    for entry in fs::read_dir(dir_path).at(dir_path)? {
+        let entry = entry.at(dir_path)?;
+        let entry_path = entry.path();
+        let file_type = entry.file_type().at(&entry_path)?;
+        if file_type.is_dir() {
+            removed += remove_temp_files(&entry_path)?;
+        } else if file_type.is_file()
+            && entry.file_name().to_string_lossy().starts_with(TEMP_PREFIX)
+        {
+            fs::remove_file(&entry_path).at(&entry_path)?;
+            debug!("Removed stale temporary file {}", entry_path.display());
+            removed += 1;
         }
     }
+    Ok(removed)
+}
 
-    if let Some(ref name) = file_config.file_name {
-        let re = Regex::new(name).unwrap();
-        if !re.is_match(file_path.file_name().unwrap().to_str().unwrap()) {
-            return false;
+// FICLONE from linux/fs.h
+nix::ioctl_write_int!(ficlone, 0x94, 9);
+
+/// Make `dest` share the data of `source` instead of copying it. Only works
+/// within one file system that supports copy-on-write, such as btrfs or XFS.
+fn reflink(source: &File, dest: &File) -> bool {
+    // SAFETY: both descriptors are open for the duration of the call.
+    unsafe { ficlone(dest.as_raw_fd(), source.as_raw_fd() as u64) }.is_ok()
+}
+
+/// Copy everything from `source` to `dest`, hashing the data as it is
+/// written. Returns the number of bytes copied.
+fn copy_data(
+    source: &mut impl Read,
+    source_path: &Path,
+    dest: &mut File,
+    dest_path: &Path,
+    hasher: &mut hash::Hasher,
+    buffer: &mut [u8],
+) -> Result<u64, Error> {
+    let mut bytes = 0;
+    loop {
+        let bytes_read = source.read(buffer).at(source_path)?;
+        if bytes_read == 0 {
+            return Ok(bytes);
         }
+        hasher.update(&buffer[..bytes_read]);
+        dest.write_all(&buffer[..bytes_read]).at(dest_path)?;
+        bytes += bytes_read as u64;
     }
+}
 
-    if let Some(ref date) = file_config.date {
-        if !date.is_empty() {
-            let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
-            let file_date = metadata.modified().unwrap();
-            let file_date = chrono::DateTime::<chrono::Local>::from(file_date)
-                .naive_local()
-                .date();
-            if file_date != date {
-                return false;
+/// Copy a regular file, its permissions and preserved extended attributes
+/// to `temp_path`, hashing the data as it is written. Errors writing the copy
+/// are about `dest_path`, where it is going to be renamed. Returns the number
+/// of bytes copied, the digest and whether the copy is a reflink.
+fn copy_file(
+    source_path: &Path,
+    dest_path: &Path,
+    temp_path: &Path,
+    copy_config: &config::CopyConfig,
+) -> Result<(u64, String, bool), Error> {
+    let mut source = File::open(source_path).at(source_path)?;
+    let metadata = source.metadata().at(source_path)?;
+    let attributes = xattrs::read(source_path).at(source_path)?;
+    let mut dest = File::create(temp_path).at(dest_path)?;
+
+    // A reflink copies nothing, but the source is still read for its digest.
+    if copy_config.reflink && reflink(&source, &dest) {
+        let digest = hash::hash_reader(&source, copy_config.hash).at(source_path)?;
+        dest.set_permissions(metadata.permissions()).at(dest_path)?;
+        xattrs::apply(temp_path, &attributes).at(dest_path)?;
+        dest.sync_all().at(dest_path)?;
+        return Ok((metadata.len(), digest, true));
+    }
+
+    let mut buffer = vec![0; hash::BUFFER_SIZE];
+    let mut hasher = hash::Hasher::new(copy_config.hash);
+    let bytes = match sparse::data_segments(&source, &metadata).at(source_path)? {
+        // Only the data is written. Seeking over the holes leaves them
+        // unallocated in the copy as well.
+        Some(segments) => {
+            let mut position = 0;
+            for (offset, length) in segments {
+                hasher.update_zeros(offset - position);
+                source.seek(io::SeekFrom::Start(offset)).at(source_path)?;
+                dest.seek(io::SeekFrom::Start(offset)).at(dest_path)?;
+                let mut segment = (&source).take(length);
+                position = offset
+                    + copy_data(
+                        &mut segment,
+                        source_path,
+                        &mut dest,
+                        dest_path,
+                        &mut hasher,
+                        &mut buffer,
+                    )?;
             }
+            hasher.update_zeros(metadata.len().saturating_sub(position));
+            dest.set_len(metadata.len()).at(dest_path)?;
+            metadata.len()
         }
+        None => copy_data(
+            &mut source,
+            source_path,
+            &mut dest,
+            dest_path,
+            &mut hasher,
+            &mut buffer,
+        )?,
+    };
+
+    dest.set_permissions(metadata.permissions()).at(dest_path)?;
+    xattrs::apply(temp_path, &attributes).at(dest_path)?;
+    dest.sync_all().at(dest_path)?;
+    Ok((bytes, hasher.finalize(), false))
+}
+
+/// What changes when a file is written to: its size, modification time and
+/// status change time.
+fn version(metadata: &fs::Metadata) -> (u64, i64, i64, i64, i64) {
+    (
+        metadata.len(),
+        metadata.mtime(),
+        metadata.mtime_nsec(),
+        metadata.ctime(),
+        metadata.ctime_nsec(),
+    )
+}
+
+fn verify_copy(
+    dest_path: &Path,
+    bytes: u64,
+    digest: &str,
+    copy_config: &config::CopyConfig,
+) -> Result<bool, std::io::Error> {
+    match copy_config.verify {
+        config::Verify::None => Ok(true),
+        config::Verify::Size => Ok(fs::metadata(dest_path)?.len() == bytes),
+        config::Verify::Reread => Ok(hash::hash_file(dest_path, copy_config.hash)? == digest),
     }
+}
 
-    if let Some(size) = file_config.size {
-        if size != 0 {
-            let file_size = metadata.len() as i64;
-            if file_size < size {
-                return false;
-            }
+/// What happened to a single entry. Fatal errors are returned as `Err` instead.
+enum Outcome {
+    Copied {
+        bytes: u64,
+        digest: String,
+        reflinked: bool,
+        inconsistent: bool,
+    },
+    Special(&'static str),
+    /// Only the metadata was recorded, and a placeholder made if asked for
+    Recorded {
+        placeholder: bool,
+    },
+    Linked,
+    Failed(Error),
+}
+
+/// Remove whatever is at `path`, a whole directory tree included.
+fn remove_existing(path: &Path) -> Result<(), io::Error> {
+    let result = match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(path),
+        Ok(_) => fs::remove_file(path),
+        Err(e) => Err(e),
+    };
+    match result {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// Create the directories above `dest_path`, the copy of `relative`. Anything
+/// else in their place was left by a backup from before the source changed
+/// type, and is replaced. Symlinks are replaced too, so that nothing is ever
+/// written through them.
+fn create_parents(dest_path: &Path, relative: &Path) -> Result<(), Error> {
+    let depth = relative.components().count().saturating_sub(1);
+    let dirs: Vec<&Path> = dest_path.ancestors().skip(1).take(depth).collect();
+    for dir in dirs.into_iter().rev() {
+        match fs::symlink_metadata(dir) {
+            Ok(metadata) if metadata.is_dir() => continue,
+            Ok(_) => remove_existing(dir).at(dir)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e).at(dir),
+        }
+        // Another worker may have just created it
+        match fs::create_dir(dir) {
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists && dir.is_dir() => {}
+            result => result.at(dir)?,
         }
     }
+    Ok(())
+}
 
-    if let Some(ref user_name) = file_config.user {
-        if !user_name.is_empty() {
-            let owner_uid = metadata.uid();
-            let owner_name =
-                get_user_by_uid(owner_uid).map(|u| u.name().to_string_lossy().into_owned());
-            if owner_name != Some(user_name.clone()) {
-                return false;
-            }
+fn copy_entry(
+    entry: &Entry,
+    dest_path: &Path,
+    copy_config: &config::CopyConfig,
+) -> Result<Outcome, Error> {
+    let entry_path = &entry.path;
+    let metadata = &entry.metadata;
+
+    // Whatever an earlier backup left in the way is replaced. The copy of a
+    // regular file is renamed over an earlier one, so only an entry of
+    // another type that rename cannot replace is removed first.
+    let replace = match entry.kind {
+        Kind::File => fs::symlink_metadata(dest_path).is_ok_and(|m| m.is_dir()),
+        Kind::Socket | Kind::Unknown => copy_config.placeholders && entry.kind == Kind::Socket,
+        _ => true,
+    };
+    let prepared = create_parents(dest_path, &entry.relative).and_then(|()| {
+        if replace {
+            remove_existing(dest_path).at(dest_path)
+        } else {
+            Ok(())
         }
+    });
+    if let Err(e) = prepared {
+        return Ok(Outcome::Failed(e));
     }
 
-    true
+    let kind = match entry.kind {
+        Kind::Dir => unreachable!("directories are created on demand"),
+        Kind::Symlink => {
+            let target = entry.link_target.as_deref().unwrap();
+            if let Err(e) = std::os::unix::fs::symlink(target, dest_path) {
+                return Ok(Outcome::Failed(Error::Io {
+                    path: dest_path.to_path_buf(),
+                    source: e,
+                }));
+            }
+            return Ok(Outcome::Special("symlink"));
+        }
+        Kind::Fifo => {
+            if let Err(e) = nix::unistd::mkfifo(dest_path, nix::sys::stat::Mode::S_IRWXU) {
+                return Ok(Outcome::Failed(Error::Io {
+                    path: dest_path.to_path_buf(),
+                    source: e.into(),
+                }));
+            }
+            "FIFO"
+        }
+        Kind::CharDevice | Kind::BlockDevice => {
+            let file_type = match entry.kind {
+                Kind::CharDevice => SFlag::S_IFCHR,
+                _ => SFlag::S_IFBLK,
+            };
+            let mode = Mode::from_bits_truncate(metadata.mode());
+            match mknod(dest_path, file_type, mode, Device::of(metadata).dev()) {
+                Ok(()) => {}
+                // Only privileged runs can create device nodes. The manifest
+                // still has everything needed to recreate them.
+                Err(Errno::EPERM) => {
+                    debug!("Not allowed to create device node {}", dest_path.display());
+                    return Ok(Outcome::Recorded { placeholder: false });
+                }
+                Err(e) => {
+                    return Ok(Outcome::Failed(Error::Io {
+                        path: dest_path.to_path_buf(),
+                        source: e.into(),
+                    }));
+                }
+            }
+            entry.kind.as_str()
+        }
+        // A socket only works while a process listens on it, so it can at
+        // most be recreated as an unbound node. Other types cannot be
+        // recreated at all.
+        Kind::Socket | Kind::Unknown => {
+            if !(copy_config.placeholders && entry.kind == Kind::Socket) {
+                return Ok(Outcome::Recorded { placeholder: false });
+            }
+            let mode = nix::sys::stat::Mode::from_bits_truncate(metadata.mode());
+            if let Err(e) = mknod(dest_path, nix::sys::stat::SFlag::S_IFSOCK, mode, 0) {
+                return Ok(Outcome::Failed(Error::Io {
+                    path: dest_path.to_path_buf(),
+                    source: e.into(),
+                }));
+            }
+            return Ok(Outcome::Recorded { placeholder: true });
+        }
+        Kind::File => {
+            // The copy only replaces `dest_path` once it is complete and
+            // verified, so an interrupted run never leaves a truncated file
+            // under the final name.
+            let temp_path = temp_path(dest_path);
+            // A file written to while it is copied may leave a copy that mixes
+            // old and new contents. It is copied again until it holds still,
+            // and kept anyway, flagged, when it never does.
+            let mut attempt = 0;
+            let (bytes, digest, reflinked, inconsistent) = loop {
+                let before = fs::metadata(entry_path).map(|metadata| version(&metadata));
+                let (bytes, digest, reflinked) =
+                    match copy_file(entry_path, dest_path, &temp_path, copy_config) {
+                        Ok(copied) => copied,
+                        Err(e) => {
+                            fs::remove_file(&temp_path).ok();
+                            return Ok(Outcome::Failed(e));
+                        }
+                    };
+                let after = fs::metadata(entry_path).map(|metadata| version(&metadata));
+                let stable = matches!((before, after), (Ok(before), Ok(after)) if before == after);
+                if stable || attempt == copy_config.retries {
+                    break (bytes, digest, reflinked, !stable);
+                }
+                attempt += 1;
+                debug!(
+                    "{} changed while it was copied, copying it again ({}/{})",
+                    entry_path.display(),
+                    attempt,
+                    copy_config.retries
+                );
+            };
+
+            let error = match verify_copy(&temp_path, bytes, &digest, copy_config) {
+                Ok(true) => match fs::rename(&temp_path, dest_path) {
+                    Ok(()) => {
+                        return Ok(Outcome::Copied {
+                            bytes,
+                            digest,
+                            reflinked,
+                            inconsistent,
+                        });
+                    }
+                    Err(e) => Error::Io {
+                        path: dest_path.to_path_buf(),
+                        source: e,
+                    },
+                },
+                Ok(false) => Error::Integrity {
+                    path: dest_path.to_path_buf(),
+                },
+                Err(e) => Error::Io {
+                    path: dest_path.to_path_buf(),
+                    source: e,
+                },
+            };
+            fs::remove_file(&temp_path).ok();
+            return Ok(Outcome::Failed(error));
+        }
+    };
+
+    match xattrs::read(entry_path).and_then(|attributes| xattrs::apply(dest_path, &attributes)) {
+        Ok(()) => Ok(Outcome::Special(kind)),
+        Err(e) => Ok(Outcome::Failed(Error::Io {
+            path: dest_path.to_path_buf(),
+            source: e,
+        })),
+    }
 }
 
-fn calculate_crc32(file_path: &Path) -> Result<u32, std::io::Error> {
-    let mut file = File::open(file_path)?;
-    let mut buffer = [0; 8192];
-    let mut hasher = crc32fast::Hasher::new();
-    
-    loop {
-        let bytes_read = file.read(&mut buffer)?;
-        if bytes_read == 0 {
-            break;
+fn record(
+    entry: &Entry,
+    dest_path: &Path,
+    outcome: Outcome,
+    copy_config: &config::CopyConfig,
+    report: &mut Report,
+    manifest: &mut Manifest,
+    journal: &mut Journal,
+) {
+    match outcome {
+        Outcome::Copied {
+            bytes,
+            digest,
+            reflinked,
+            inconsistent,
+        } => {
+            report.copied(bytes, sparse::allocated_bytes(&entry.metadata));
+            if reflinked {
+                report.reflinked();
+            }
+            if inconsistent {
+                report.inconsistent(&entry.path);
+            }
+            journal.record(manifest.add(entry, Some(digest.clone())));
+            emit(Event::FileCopied {
+                source: &entry.path,
+                dest: dest_path,
+                bytes,
+                algorithm: copy_config.hash.as_str(),
+                hash: digest,
+                reflinked,
+                inconsistent,
+            });
         }
-        hasher.update(&buffer[..bytes_read]);
+        Outcome::Special(kind) => {
+            report.special();
+            journal.record(manifest.add(entry, None));
+            emit(Event::SpecialCopied {
+                kind,
+                source: &entry.path,
+                dest: dest_path,
+            });
+        }
+        Outcome::Recorded { placeholder } => {
+            report.recorded();
+            if placeholder {
+                journal.record(manifest.add(entry, None));
+            } else {
+                journal.record(manifest.add_metadata_only(entry));
+            }
+            emit(Event::MetadataRecorded {
+                kind: entry.kind.as_str(),
+                source: &entry.path,
+                placeholder: placeholder.then_some(dest_path),
+            });
+        }
+        Outcome::Linked => {
+            let target = entry.hard_link.as_deref().unwrap();
+            report.linked();
+            journal.record(manifest.add_link(entry, target));
+            emit(Event::HardLinked {
+                source: &entry.path,
+                dest: dest_path,
+                target,
+            });
+        }
+        Outcome::Failed(e) => report.fail(&entry.path, e),
     }
-    
-    Ok(hasher.finalize())
 }
 
-fn verify_crc32(source_path: &Path, dest_path: &Path) -> Result<bool, std::io::Error> {
-    let source_crc = calculate_crc32(source_path)?;
-    let dest_crc = calculate_crc32(dest_path)?;
-    Ok(source_crc == dest_crc)
+/// Recreate a hard link to `target_path`, which must already be copied.
+fn link_entry(entry: &Entry, dest_path: &Path, target_path: &Path) -> Outcome {
+    let prepared = create_parents(dest_path, &entry.relative)
+        .and_then(|()| remove_existing(dest_path).at(dest_path));
+    if let Err(e) = prepared {
+        return Outcome::Failed(e);
+    }
+    match fs::hard_link(target_path, dest_path) {
+        Ok(()) => Outcome::Linked,
+        Err(e) => Outcome::Failed(Error::Io {
+            path: dest_path.to_path_buf(),
+            source: e,
+        }),
+    }
 }
 
-fn copy_dir_recursive(
-    root_path: &Path,
+/// Make the copy of a directory ready to be filled: create it when empty
+/// directories are kept, and make sure the owner can write to it if an
+/// earlier backup left it read-only.
+fn prepare_dir(
+    entry: &Entry,
+    dest_path: &Path,
+    copy_config: &config::CopyConfig,
+) -> Result<(), Error> {
+    let metadata = fs::symlink_metadata(dest_path);
+    if copy_config.keep_empty_dirs && !metadata.as_ref().is_ok_and(|m| m.is_dir()) {
+        create_parents(dest_path, &entry.relative)?;
+        remove_existing(dest_path).at(dest_path)?;
+        fs::create_dir(dest_path).at(dest_path)?;
+    }
+    if let Ok(metadata) = metadata
+        && metadata.is_dir()
+        && metadata.mode() & 0o700 != 0o700
+    {
+        let permissions = fs::Permissions::from_mode(metadata.mode() | 0o700);
+        fs::set_permissions(dest_path, permissions).at(dest_path)?;
+    }
+    Ok(())
+}
+
+/// Give a copied directory the owner, times, mode and preserved extended
+/// attributes of its source. Only root can give files away, so failing to
+/// change the owner is not an error.
+fn set_dir_metadata(
     source_path: &Path,
     dest_path: &Path,
-    file_config: &config::FileConfig,
-) -> Result<(), std::io::Error> {
-    for entry in fs::read_dir(source_path)? {
-        let entry = entry?;
-        let entry_path = entry.path();
-        let dest_path = dest_path.join(entry.file_name());
-
-        if entry_path.is_dir() {
-            fs::create_dir_all(&dest_path)?;
-            copy_dir_recursive(&root_path, &entry_path, &dest_path, file_config)?;
-            if fs::read_dir(&dest_path)
-                .map(|mut entries| entries.next().is_none())
-                .unwrap_or(false)
-            {
-                fs::remove_dir(dest_path)?;
+    metadata: &fs::Metadata,
+) -> Result<(), io::Error> {
+    let dest = fs::metadata(dest_path)?;
+    if (dest.uid(), dest.gid()) != (metadata.uid(), metadata.gid()) {
+        match std::os::unix::fs::chown(dest_path, Some(metadata.uid()), Some(metadata.gid())) {
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                debug!("Cannot change the owner of {}: {}", dest_path.display(), e);
             }
-        } else {
-            if check_file_properties(&root_path, &entry_path, file_config) {
-                let metadata = match fs::symlink_metadata(&entry_path) {
-                    Ok(metadata) => metadata,
-                    Err(_) => {
-                        eprintln!("Failed to get metadata for {:?}", &entry_path);
-                        continue;
-                    }
-                };
-                if metadata.file_type().is_symlink() {
-                    // xxx: cannot remove symlink?
-                    let target = fs::read_link(&entry_path)?;
-                    if dest_path.exists() {
-                        fs::remove_file(&dest_path)?;
-                    }
-                    if let Err(e) = std::os::unix::fs::symlink(&target, &dest_path) {
-                        eprintln!(
-                            "Failed to create symlink from {:?} to {:?}: {}",
-                            &target, &dest_path, e
-                        );
-                        continue;
-                    }
-                    println!("Copied symlink {:?} to {:?}", &entry_path, &dest_path);
-                } else if metadata.file_type().is_fifo() {
-                    if dest_path.exists() {
-                        fs::remove_file(&dest_path)?;
-                    }
-                    if let Err(e) = nix::unistd::mkfifo(&dest_path, nix::sys::stat::Mode::S_IRWXU) {
-                        eprintln!("Failed to create FIFO {:?}: {}", &dest_path, e);
-                        continue;
+            result => result?,
+        }
+    }
+    let times = fs::FileTimes::new()
+        .set_accessed(metadata.accessed()?)
+        .set_modified(metadata.modified()?);
+    File::open(dest_path)?.set_times(times)?;
+    fs::set_permissions(dest_path, metadata.permissions())?;
+    xattrs::apply(dest_path, &xattrs::read(source_path)?)
+}
+
+fn copy_entries(
+    walk: &Walk,
+    dest_path: &Path,
+    copy_config: &config::CopyConfig,
+    report: &mut Report,
+    manifest: &mut Manifest,
+    journal: &mut Journal,
+    progress: &mut Progress,
+) -> Result<(), Error> {
+    // Unless empty directories are kept, directories are created on demand,
+    // so that filtered-out subtrees do not leave empty directories behind.
+    // Hard links are made once everything they can point to has been copied.
+    let (dirs, entries): (Vec<&Entry>, Vec<&Entry>) =
+        walk.entries.iter().partition(|entry| entry.kind == Kind::Dir);
+    let (links, entries): (Vec<&Entry>, Vec<&Entry>) =
+        entries.into_iter().partition(|entry| entry.hard_link.is_some());
+    for entry in &dirs {
+        if let Err(e) = prepare_dir(entry, &dest_path.join(&entry.relative), copy_config) {
+            report.fail(&entry.path, e);
+        }
+    }
+    copy_files(
+        &entries,
+        dest_path,
+        copy_config,
+        report,
+        manifest,
+        journal,
+        progress,
+    )?;
+
+    for entry in links {
+        let target = entry.hard_link.as_deref().unwrap();
+        let entry_dest = dest_path.join(&entry.relative);
+        let outcome = link_entry(entry, &entry_dest, &dest_path.join(target));
+        record(
+            entry,
+            &entry_dest,
+            outcome,
+            copy_config,
+            report,
+            manifest,
+            journal,
+        );
+        progress.advance(entry.size());
+    }
+
+    // Directory metadata is applied last, since writing their contents
+    // changes their modification times, and deepest first, so that a
+    // read-only parent does not keep its children from being updated.
+    for entry in dirs.iter().rev() {
+        let entry_dest = dest_path.join(&entry.relative);
+        if !fs::symlink_metadata(&entry_dest).is_ok_and(|m| m.is_dir()) {
+            continue;
+        }
+        if let Err(e) = set_dir_metadata(&entry.path, &entry_dest, &entry.metadata) {
+            let error = Error::Io {
+                path: entry_dest,
+                source: e,
+            };
+            report.fail(&entry.path, error);
+        }
+    }
+    Ok(())
+}
+
+fn copy_files(
+    entries: &[&Entry],
+    dest_path: &Path,
+    copy_config: &config::CopyConfig,
+    report: &mut Report,
+    manifest: &mut Manifest,
+    journal: &mut Journal,
+    progress: &mut Progress,
+) -> Result<(), Error> {
+    let jobs = copy_config.jobs().min(entries.len()).max(1);
+
+    if jobs == 1 {
+        for entry in entries {
+            let dest_path = dest_path.join(&entry.relative);
+            let outcome = copy_entry(entry, &dest_path, copy_config)?;
+            record(
+                entry,
+                &dest_path,
+                outcome,
+                copy_config,
+                report,
+                manifest,
+                journal,
+            );
+            progress.advance(entry.size());
+        }
+        return Ok(());
+    }
+
+    // Workers take the next entry from a shared counter and send back its
+    // outcome. Outcomes are recorded in walk order, so reports and events are
+    // the same as for a serial copy, and a fatal error stops the run at the
+    // same entry.
+    let next = AtomicUsize::new(0);
+    let cancelled = AtomicBool::new(false);
+    std::thread::scope(|scope| {
+        let (tx, rx) = mpsc::channel();
+        for _ in 0..jobs {
+            let tx = tx.clone();
+            let (entries, next, cancelled) = (&entries, &next, &cancelled);
+            scope.spawn(move || {
+                while !cancelled.load(Ordering::Relaxed) {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(entry) = entries.get(index) else {
+                        break;
+                    };
+                    let outcome =
+                        copy_entry(entry, &dest_path.join(&entry.relative), copy_config);
+                    if tx.send((index, outcome)).is_err() {
+                        break;
                     }
-                    println!("Copied FIFO {:?} to {:?}", &entry_path, &dest_path);
-                } else if metadata.file_type().is_char_device() {
-                    if dest_path.exists() {
-                        fs::remove_file(&dest_path)?;
+                }
+            });
+        }
+        drop(tx);
+
+        let mut pending = BTreeMap::new();
+        let mut recorded = 0;
+        for (index, outcome) in rx {
+            pending.insert(index, outcome);
+            while let Some(outcome) = pending.remove(&recorded) {
+                let entry = entries[recorded];
+                match outcome {
+                    Ok(outcome) => record(
+                        entry,
+                        &dest_path.join(&entry.relative),
+                        outcome,
+                        copy_config,
+                        report,
+                        manifest,
+                        journal,
+                    ),
+                    Err(e) => {
+                        cancelled.store(true, Ordering::Relaxed);
+                        return Err(e);
                     }
-                    mknod(
-                        &dest_path,
-                        nix::sys::stat::SFlag::S_IFCHR,
-                        nix::sys::stat::Mode::from_bits_truncate(
-                            metadata.mode().try_into().unwrap(),
-                        ),
-                        metadata.rdev() as i32,
-                    )?;
-                    println!("Copied char device {:?} to {:?}", &entry_path, &dest_path);
-                } else if metadata.file_type().is_block_device() {
-                    if dest_path.exists() {
-                        fs::remove_file(&dest_path)?;
+                }
+                progress.advance(entry.size());
+                recorded += 1;
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Remove from `walk` the entries that an interrupted run already backed up
+/// and that have not changed since, and add them to the manifest as they are.
+fn skip_finished(
+    walk: &mut Walk,
+    dest_path: &Path,
+    finished: HashMap<PathBuf, ManifestEntry>,
+    report: &mut Report,
+    manifest: &mut Manifest,
+) {
+    if finished.is_empty() {
+        return;
+    }
+    let (mut files, mut bytes) = (0, 0);
+    walk.entries.retain(|entry| {
+        let Some(done) = finished.get(&entry.relative) else {
+            return true;
+        };
+        let unchanged = done.kind == entry.kind
+            && done.size == entry.size()
+            && done.mtime == entry.metadata.mtime()
+            && done.mtime_nsec == entry.metadata.mtime_nsec()
+            && fs::symlink_metadata(dest_path.join(&entry.relative)).is_ok();
+        if !unchanged {
+            return true;
+        }
+        files += 1;
+        bytes += entry.size();
+        report.resumed();
+        manifest.entries.push(done.clone());
+        false
+    });
+    walk.files -= files;
+    walk.bytes -= bytes;
+}
+
+/// Write a PAX extended header holding preserved extended attributes, which
+/// then apply to the entry appended next.
+fn append_xattrs<W: Write>(
+    tar_builder: &mut tar::Builder<W>,
+    attributes: &xattrs::Attributes,
+) -> io::Result<()> {
+    if attributes.is_empty() {
+        return Ok(());
+    }
+    let records = xattrs::pax_records(attributes);
+    let mut header = tar::Header::new_ustar();
+    header.set_path("././@PaxHeader")?;
+    header.set_entry_type(tar::EntryType::XHeader);
+    header.set_mode(0o644);
+    header.set_size(records.len() as u64);
+    header.set_cksum();
+    tar_builder.append(&header, records.as_slice())
+}
+
+/// What is read from the source to archive an entry.
+struct Opened {
+    attributes: xattrs::Attributes,
+    /// A regular file and its data segments, if it is sparse
+    file: Option<(File, Option<Vec<sparse::Segment>>)>,
+}
+
+fn open_entry(entry: &Entry) -> Result<Opened, io::Error> {
+    let attributes = if entry.kind != Kind::Symlink && !entry.kind.metadata_only() {
+        xattrs::read(&entry.path)?
+    } else {
+        Vec::new()
+    };
+    let file = match entry.kind {
+        Kind::File => {
+            let file = File::open(&entry.path)?;
+            let segments = sparse::data_segments(&file, &entry.metadata)?;
+            Some((file, segments))
+        }
+        _ => None,
+    };
+    Ok(Opened { attributes, file })
+}
+
+fn append_entries<W: Write>(
+    tar_builder: &mut tar::Builder<W>,
+    walk: &Walk,
+    algorithm: hash::Algorithm,
+    report: &mut Report,
+    manifest: &mut Manifest,
+    progress: &mut Progress,
+) -> Result<(), io::Error> {
+    // Symlinks that are kept are appended explicitly with their target from
+    // the walk. Any other entry reached through a symlink was followed.
+    tar_builder.follow_symlinks(true);
+    for entry in &walk.entries {
+        if let Some(target) = &entry.hard_link {
+            let mut header = tar::Header::new_gnu();
+            header.set_metadata_in_mode(&entry.metadata, tar::HeaderMode::Complete);
+            header.set_entry_type(tar::EntryType::Link);
+            header.set_size(0);
+            tar_builder.append_link(&mut header, &entry.relative, target)?;
+            report.linked();
+            manifest.add_link(entry, target);
+            emit(Event::HardLinked {
+                source: &entry.path,
+                dest: &entry.relative,
+                target,
+            });
+            progress.advance(entry.size());
+            continue;
+        }
+
+        // Whatever can fail for this entry alone is read before anything is
+        // written, so that it can be left out of the archive like an
+        // unreadable file is left out of a plain copy.
+        let opened = match open_entry(entry) {
+            Ok(opened) => opened,
+            Err(e) => {
+                let error = Error::Io {
+                    path: entry.path.clone(),
+                    source: e,
+                };
+                report.fail(&entry.path, error);
+                progress.advance(entry.size());
+                continue;
+            }
+        };
+        append_xattrs(tar_builder, &opened.attributes)?;
+        match entry.kind {
+            Kind::Dir => {
+                tar_builder.append_path_with_name(&entry.path, &entry.relative)?;
+                continue;
+            }
+            Kind::File => {
+                // Hash the contents while they are archived, so the manifest
+                // describes exactly what went into the tar.
+                let (file, segments) = opened.file.unwrap();
+                let mut header = tar::Header::new_gnu();
+                header.set_metadata_in_mode(&entry.metadata, tar::HeaderMode::Complete);
+                let digest = match segments {
+                    Some(segments) => {
+                        let extensions =
+                            sparse::set_sparse_header(&mut header, &segments, entry.size());
+                        let mut reader =
+                            sparse::SegmentReader::new(file, &segments, entry.size(), algorithm);
+                        let data = extensions.as_slice().chain(&mut reader);
+                        tar_builder.append_data(&mut header, &entry.relative, data)?;
+                        reader.finalize()
                     }
-                    mknod(
-                        &dest_path,
-                        nix::sys::stat::SFlag::S_IFBLK,
-                        nix::sys::stat::Mode::from_bits_truncate(
-                            metadata.mode().try_into().unwrap(),
-                        ),
-                        metadata.rdev() as i32,
-                    )?;
-                    println!("Copied block device {:?} to {:?}", &entry_path, &dest_path);
-                } else {
-                    match fs::copy(&entry_path, &dest_path) {
-                        Ok(_) => {
-                            // Verify CRC32 checksum
-                            match verify_crc32(&entry_path, &dest_path) {
-                                Ok(true) => {
-                                    let crc = calculate_crc32(&entry_path).unwrap_or(0);
-                                    println!("Copied {:?} to {:?} (CRC32: {:08x})", &entry_path, &dest_path, crc);
-                                }
-                                Ok(false) => {
-                                    eprintln!(
-                                        "CRC32 mismatch for {:?} - backup may be corrupted!",
-                                        &dest_path
-                                    );
-                                    fs::remove_file(&dest_path).ok();
-                                    continue;
-                                }
-                                Err(e) => {
-                                    eprintln!(
-                                        "Failed to verify CRC32 for {:?}: {}",
-                                        &dest_path, e
-                                    );
-                                    continue;
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            eprintln!(
-                                "Failed to copy {:?} to {:?}: {}",
-                                &entry_path, &dest_path, e
-                            );
-                            continue;
-                        }
+                    None => {
+                        // Exactly the size in the header is archived, cut or
+                        // padded with zeros if the file changed since the walk.
+                        let data = file.take(entry.size()).chain(io::repeat(0)).take(entry.size());
+                        let mut reader = hash::HashingReader::new(data, algorithm);
+                        tar_builder.append_data(&mut header, &entry.relative, &mut reader)?;
+                        reader.finalize()
                     }
+                };
+                report.copied(entry.size(), sparse::allocated_bytes(&entry.metadata));
+                // The header is already written, so an archived file cannot
+                // be retried, only flagged.
+                let inconsistent = !fs::metadata(&entry.path)
+                    .is_ok_and(|after| version(&after) == version(&entry.metadata));
+                if inconsistent {
+                    report.inconsistent(&entry.path);
                 }
+                manifest.add(entry, Some(digest.clone()));
+                emit(Event::FileCopied {
+                    source: &entry.path,
+                    dest: &entry.relative,
+                    bytes: entry.size(),
+                    algorithm: algorithm.as_str(),
+                    hash: digest,
+                    reflinked: false,
+                    inconsistent,
+                });
+            }
+            Kind::Symlink => {
+                let mut header = tar::Header::new_gnu();
+                header.set_metadata_in_mode(&entry.metadata, tar::HeaderMode::Complete);
+                header.set_size(0);
+                let target = entry.link_target.as_deref().unwrap();
+                tar_builder.append_link(&mut header, &entry.relative, target)?;
+                report.special();
+                manifest.add(entry, None);
+                emit(Event::SpecialCopied {
+                    kind: entry.kind.as_str(),
+                    source: &entry.path,
+                    dest: &entry.relative,
+                });
+            }
+            // Tar has no entry type for these
+            Kind::Socket | Kind::Unknown => {
+                report.recorded();
+                manifest.add_metadata_only(entry);
+                emit(Event::MetadataRecorded {
+                    kind: entry.kind.as_str(),
+                    source: &entry.path,
+                    placeholder: None,
+                });
+            }
+            // The tar crate stores these under their source path whatever
+            // name they are given, so their header is built here.
+            Kind::Fifo | Kind::CharDevice | Kind::BlockDevice => {
+                let mut header = tar::Header::new_gnu();
+                header.set_metadata_in_mode(&entry.metadata, tar::HeaderMode::Complete);
+                header.set_size(0);
+                header.set_entry_type(match entry.kind {
+                    Kind::Fifo => tar::EntryType::Fifo,
+                    Kind::CharDevice => tar::EntryType::Char,
+                    _ => tar::EntryType::Block,
+                });
+                if entry.kind != Kind::Fifo {
+                    let device = Device::of(&entry.metadata);
+                    header.set_device_major(device.major as u32)?;
+                    header.set_device_minor(device.minor as u32)?;
+                }
+                tar_builder.append_data(&mut header, &entry.relative, io::empty())?;
+                report.special();
+                manifest.add(entry, None);
+                emit(Event::SpecialCopied {
+                    kind: entry.kind.as_str(),
+                    source: &entry.path,
+                    dest: &entry.relative,
+                });
             }
         }
+        progress.advance(entry.size());
+    }
+    Ok(())
+}
+
+fn write_archive(
+    walk: &Walk,
+    tar_path: &Path,
+    config: &Config,
+    report: &mut Report,
+    manifest: &mut Manifest,
+    progress: &mut Progress,
+) -> Result<(), io::Error> {
+    let output_config = &config.output_config;
+    let algorithm = config.copy_config.hash;
+    let temp_path = temp_path(tar_path);
+    let tar_file = File::create(&temp_path)?;
+
+    let result = if output_config.gzip {
+        let encoder = GzEncoder::new(tar_file, Compression::default());
+        let mut tar_builder = tar::Builder::new(encoder);
+        append_entries(
+            &mut tar_builder,
+            walk,
+            algorithm,
+            report,
+            manifest,
+            progress,
+        )
+        .and_then(|()| tar_builder.into_inner()?.finish()?.sync_all())
+    } else {
+        let mut tar_builder = tar::Builder::new(tar_file);
+        append_entries(
+            &mut tar_builder,
+            walk,
+            algorithm,
+            report,
+            manifest,
+            progress,
+        )
+        .and_then(|()| tar_builder.into_inner()?.sync_all())
+    };
+    if let Err(e) = result.and_then(|()| fs::rename(&temp_path, tar_path)) {
+        fs::remove_file(&temp_path).ok();
+        return Err(e);
     }
+    progress.finish();
+    emit(Event::ArchiveCreated {
+        path: tar_path,
+        gzip: output_config.gzip,
+    });
     Ok(())
 }
 
 fn backup_files(
-    source_path: &PathBuf,
-    dest_path: &PathBuf,
-    file_config: &config::FileConfig,
-    output_config: &config::OutputConfig,
-) -> Result<(), std::io::Error> {
-    println!("Backing up files...");
-    
+    source_path: &Path,
+    dest_path: &Path,
+    config: &Config,
+    show_progress: bool,
+    resume: bool,
+) -> Result<(), Error> {
+    info!("Backing up files...");
+    let output_config = &config.output_config;
+    let mut report = Report::new(source_path, dest_path);
+    let mut manifest = Manifest::new(config.copy_config.hash);
+    let removed = remove_temp_files(dest_path)?;
+    if removed > 0 {
+        info!(
+            "Removed {} temporary file(s) left by an interrupted backup.",
+            removed
+        );
+    }
+    // Archives hold the whole tree, as they always have; the file filters
+    // only select what plain copies contain.
+    let filtered = !output_config.tar;
+    let mut walk = walk::walk(source_path, &config.file_config, filtered, &mut report)?;
+
+    let mut journal = None;
     if output_config.tar {
+        if resume {
+            warn!("Archive backups cannot be resumed, starting over.");
+        }
+        let mut progress = Progress::new(walk.files, walk.bytes, show_progress);
         let tar_path = if output_config.gzip {
             dest_path.join("backup.tar.gz")
         } else {
             dest_path.join("backup.tar")
         };
-        
-        let tar_file = File::create(&tar_path)?;
-        
-        if output_config.gzip {
-            let encoder = GzEncoder::new(tar_file, Compression::default());
-            let mut tar_builder = tar::Builder::new(encoder);
-            tar_builder.follow_symlinks(false);
-            tar_builder.append_dir_all(".", source_path)?;
-            tar_builder.finish()?;
-            println!("Created gzipped tar archive at {:?}", tar_path);
-        } else {
-            let mut tar_builder = tar::Builder::new(tar_file);
-            tar_builder.follow_symlinks(false);
-            tar_builder.append_dir_all(".", source_path)?;
-            tar_builder.finish()?;
-            println!("Created tar archive at {:?}", tar_path);
-        }
-        return Ok(());
+
+        write_archive(
+            &walk,
+            &tar_path,
+            config,
+            &mut report,
+            &mut manifest,
+            &mut progress,
+        )
+        .map_err(|source| Error::Archive {
+            path: tar_path.clone(),
+            source,
+        })?;
+        manifest.archive = tar_path.file_name().map(PathBuf::from);
+        report.archive = Some(tar_path);
     } else {
-        copy_dir_recursive(source_path, source_path, dest_path, file_config)?;
+        let (mut plain_journal, finished) =
+            Journal::open(dest_path, resume, config.copy_config.hash)?;
+        skip_finished(&mut walk, dest_path, finished, &mut report, &mut manifest);
+        let mut progress = Progress::new(walk.files, walk.bytes, show_progress);
+        let result = copy_entries(
+            &walk,
+            dest_path,
+            &config.copy_config,
+            &mut report,
+            &mut manifest,
+            &mut plain_journal,
+            &mut progress,
+        );
+        progress.finish();
+        result?;
+        journal = Some(plain_journal);
+    }
+
+    report.finish();
+    emit(Event::Summary { report: &report });
+    manifest.save(dest_path)?;
+    if let Some(journal) = journal {
+        journal.remove()?;
+    }
+    if output_config.report {
+        report.save()?;
+    }
+
+    if !report.failures.is_empty() {
+        warn!(
+            "Backup completed with {} file(s) that could not be backed up.",
+            report.failures.len()
+        );
+        return Err(Error::Partial {
+            failed: report.failures.len(),
+        });
     }
-    
-    println!("Backup completed successfully.");
+
+    info!("Backup completed successfully.");
     Ok(())
 }
 
-fn get_source_directory(config: &mut Config) -> Result<PathBuf, std::io::Error> {
-    println!(
+pub fn get_source_directory(config: &Config) -> Result<PathBuf, Error> {
+    debug!(
         "Source directory got from config at {}",
         config.path_config.source_path
     );
@@ -270,19 +1086,22 @@ fn get_source_directory(config: &mut Config) -> Result<PathBuf, std::io::Error>
     if path.exists() && path.is_dir() {
         Ok(path.to_path_buf())
     } else {
-        eprintln!(
+        error!(
             "Source directory {} does not exist or is not a directory.",
             path.display()
         );
-        Err(std::io::Error::new(
-            std::io::ErrorKind::NotFound,
-            "Source directory not found or invalid",
-        ))
+        Err(Error::Io {
+            path: path.to_path_buf(),
+            source: std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "Source directory not found or invalid",
+            ),
+        })
     }
 }
 
-fn get_dest_directory(config: &mut Config) -> Result<PathBuf, std::io::Error> {
-    println!(
+fn get_dest_directory(config: &Config) -> Result<PathBuf, Error> {
+    debug!(
         "Destination directory got from config at {}",
         config.path_config.dest_path
     );
@@ -292,52 +1111,47 @@ fn get_dest_directory(config: &mut Config) -> Result<PathBuf, std::io::Error> {
     } else {
         if !path.exists() {
             if let Err(err) = fs::create_dir_all(path) {
-                eprintln!("Cannot create {}: {}", path.display(), err);
-                return Err(err);
+                error!("Cannot create {}: {}", path.display(), err);
+                return Err(err).at(path);
             }
             return Ok(path.to_path_buf());
         }
-        eprintln!(
+        error!(
             "Destination directory {} is not a directory.",
             path.display()
         );
-        Err(std::io::Error::new(
-            std::io::ErrorKind::NotFound,
-            "Destination directory invalid",
-        ))
+        Err(Error::Io {
+            path: path.to_path_buf(),
+            source: std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "Destination directory invalid",
+            ),
+        })
     }
 }
 
-fn check_directories(config: &mut Config) -> Result<(PathBuf, PathBuf), std::io::Error> {
+fn check_directories(config: &Config) -> Result<(PathBuf, PathBuf), Error> {
     let source_path = get_source_directory(config)?;
     let dest_path = get_dest_directory(config)?;
 
-    println!("Directories read successfully.");
+    debug!("Directories read successfully.");
     Ok((source_path, dest_path))
 }
 
 fn timed_backup(
-    source_path: &PathBuf,
-    dest_path: &PathBuf,
-    file_config: &config::FileConfig,
-    output_config: &config::OutputConfig,
+    source_path: &Path,
+    dest_path: &Path,
+    config: &Config,
     interval: u64,
-) -> Result<(), std::io::Error> {
-    if interval == 0 {
-        eprintln!("Interval must be greater than 0.");
-        return Err(std::io::Error::new(
-            std::io::ErrorKind::InvalidInput,
-            "Interval must be greater than 0",
-        ));
-    }
-
-    println!("Starting timer with interval of {} seconds...", interval);
+    show_progress: bool,
+) -> Result<(), Error> {
+    info!("Starting timer with interval of {} seconds...", interval);
     loop {
         let start = std::time::Instant::now();
-        println!("Running timer backup...");
+        info!("Running timer backup...");
 
-        if let Err(err) = backup_files(&source_path, &dest_path, file_config, output_config) {
-            eprintln!("Backup command failed: {}", err);
+        if let Err(err) = backup_files(source_path, dest_path, config, show_progress, false) {
+            error!("Backup command failed: {}", err);
         }
 
         let elapsed = start.elapsed();
@@ -349,12 +1163,12 @@ fn timed_backup(
 }
 
 fn realtime_backup(
-    source_path: &PathBuf,
-    dest_path: &PathBuf,
-    file_config: &config::FileConfig,
-    output_config: &config::OutputConfig,
-) -> Result<(), std::io::Error> {
-    println!("Starting real-time backup...");
+    source_path: &Path,
+    dest_path: &Path,
+    config: &Config,
+    show_progress: bool,
+) -> Result<(), Error> {
+    info!("Starting real-time backup...");
 
     let (tx, rx) = std::sync::mpsc::channel();
 
@@ -363,45 +1177,47 @@ fn realtime_backup(
             Ok(event) => {
                 tx.send(event).unwrap();
             }
-            Err(e) => eprintln!("watch error: {:?}", e),
+            Err(e) => warn!("watch error: {:?}", e),
         })
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+        .map_err(std::io::Error::other)
+        .at(source_path)?;
 
     watcher
-        .watch(&source_path, notify::RecursiveMode::Recursive)
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+        .watch(source_path, notify::RecursiveMode::Recursive)
+        .map_err(std::io::Error::other)
+        .at(source_path)?;
 
     loop {
         match rx.recv() {
             Ok(event) => {
-                println!("Change detected: {:?}", event);
-                if let Err(err) = backup_files(&source_path, &dest_path, file_config, output_config)
+                debug!("Change detected: {:?}", event);
+                if let Err(err) =
+                    backup_files(source_path, dest_path, config, show_progress, false)
                 {
-                    eprintln!("Backup command failed: {}", err);
+                    error!("Backup command failed: {}", err);
                 }
             }
-            Err(e) => eprintln!("recv error: {:?}", e),
+            Err(e) => warn!("recv error: {:?}", e),
         }
     }
 }
 
-pub fn command_backup(args: &BackupArgs) -> Result<(), std::io::Error> {
-    let mut config = config::get_config(&args.config_path)?;
-    let (source_path, dest_path) = check_directories(&mut config)?;
-    let file_config = &config.file_config;
-    let output_config = &config.output_config;
+pub fn command_backup(args: &BackupArgs) -> Result<(), Error> {
+    let config = config::get_config(&args.config_path)?;
+    let (source_path, dest_path) = check_directories(&config)?;
+    let show_progress = !args.no_progress;
 
     if args.realtime {
-        realtime_backup(&source_path, &dest_path, file_config, output_config)
+        realtime_backup(&source_path, &dest_path, &config, show_progress)
     } else if let Some(interval) = args.interval {
-        timed_backup(
+        timed_backup(&source_path, &dest_path, &config, interval, show_progress)
+    } else {
+        backup_files(
             &source_path,
             &dest_path,
-            file_config,
-            output_config,
-            interval,
+            &config,
+            show_progress,
+            args.resume,
         )
-    } else {
-        backup_files(&source_path, &dest_path, file_config, output_config)
     }
 }
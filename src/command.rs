@@ -1,4 +1,5 @@
 use crate::*;
+use log::info;
 
 // Rust program to backup files in your directories
 #[derive(Parser)]
@@ -9,6 +10,22 @@ use crate::*;
 pub struct Args {
     #[command(subcommand)]
     pub command: Option<Commands>,
+
+    /// Output format
+    #[arg(long, global = true, value_enum, default_value_t)]
+    pub format: output::Format,
+
+    /// Show more output (-v for debug, -vv for trace)
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Show less output (-q for warnings and errors, -qq for errors only)
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    pub quiet: u8,
+
+    /// Also write log messages to a file
+    #[arg(long, global = true, value_name = "FILE")]
+    pub log_file: Option<PathBuf>,
 }
 
 #[derive(Subcommand)]
@@ -19,28 +36,41 @@ pub enum Commands {
     /// Backup files
     Backup(backup::BackupArgs),
 
+    /// Check a backup against its manifest
+    Verify(verify::VerifyArgs),
+
+    /// Show what changed in the source since the last backup
+    Diff(diff::DiffArgs),
+
     /// Edit configuration file
-    Config(config::ConfigArgs),
+    Config(Box<config::ConfigArgs>),
 
     /// Reset configuration file to default values
     Reset(config::ResetArgs),
 }
 
-pub fn execute_command(args: Args) -> Result<(), io::Error> {
+pub fn execute_command(args: Args) -> Result<(), Error> {
+    output::set_format(args.format);
+    logger::apply(args.verbose, args.quiet, args.log_file.as_deref())?;
+
     match &args.command {
         Some(Commands::Exit) => {
-            println!("Exiting the program.");
+            info!("Exiting the program.");
             std::process::exit(0);
         }
 
         Some(Commands::Backup(args)) => backup::command_backup(args),
 
+        Some(Commands::Verify(args)) => verify::command_verify(args),
+
+        Some(Commands::Diff(args)) => diff::command_diff(args),
+
         Some(Commands::Config(args)) => config::command_config(args),
 
         Some(Commands::Reset(args)) => config::command_reset(args),
 
         None => {
-            println!("No command provided. Use --help for more information.");
+            info!("No command provided. Use --help for more information.");
             Ok(())
         }
     }
@@ -49,11 +79,5 @@ pub fn execute_command(args: Args) -> Result<(), io::Error> {
 pub fn get_args(line: String) -> Result<Args, clap::Error> {
     let tokens = line.split_whitespace();
     let argv = std::iter::once("myprog").chain(tokens);
-    let args = match Args::try_parse_from(argv) {
-        Ok(a) => a,
-        Err(e) => {
-            return Err(e);
-        }
-    };
-    Ok(args)
+    Args::try_parse_from(argv)
 }
@@ -1,4 +1,6 @@
 use crate::*;
+use log::{debug, error, info};
+use output::{Event, emit};
 use std::sync::{Mutex, OnceLock};
 
 #[derive(Deserialize, Serialize)]
@@ -14,12 +16,142 @@ pub struct FileConfig {
     pub date: Option<String>,
     pub size: Option<i64>,
     pub user: Option<String>,
+    #[serde(default)]
+    pub symlinks: Symlinks,
+    /// Do not descend into directories on other file systems
+    #[serde(default)]
+    pub one_file_system: bool,
+    /// Also skip tmpfs mounts, like other in-memory file systems
+    #[serde(default)]
+    pub skip_tmpfs: bool,
+    /// How many directory levels below the source to back up
+    #[serde(default)]
+    pub max_depth: Option<usize>,
+    /// Files whose presence excludes the directory holding them
+    #[serde(default = "default_exclude_markers")]
+    pub exclude_markers: Vec<String>,
+    /// Apply the patterns of .gitignore, .ignore and .babycancerignore files
+    #[serde(default)]
+    pub ignore_files: bool,
+    #[serde(default)]
+    pub name_matching: NameMatching,
+}
+
+fn default_exclude_markers() -> Vec<String> {
+    vec![".nobackup".to_string(), "CACHEDIR.TAG".to_string()]
 }
 
 #[derive(Deserialize, Serialize)]
 pub struct OutputConfig {
     pub tar: bool,
     pub gzip: bool,
+    #[serde(default)]
+    pub report: bool,
+}
+
+/// What to do with symbolic links found in the source.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Symlinks {
+    /// Back up links as links, pointing where they did
+    #[default]
+    Preserve,
+    /// Back up what links point to in their place
+    Follow,
+    /// Leave links out of the backup
+    Skip,
+    /// Back up links as links, rewritten to stay inside the backup when
+    /// they point inside the source
+    Rewrite,
+}
+
+impl Symlinks {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Symlinks::Preserve => "preserve",
+            Symlinks::Follow => "follow",
+            Symlinks::Skip => "skip",
+            Symlinks::Rewrite => "rewrite",
+        }
+    }
+}
+
+/// How the file name pattern is matched against names that are not valid
+/// UTF-8.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum NameMatching {
+    /// Match the raw bytes of names, so that a pattern like `(?-u:\xE9)`
+    /// can select a byte that is not UTF-8
+    #[default]
+    Bytes,
+    /// Match names with their invalid sequences replaced by U+FFFD
+    Lossy,
+}
+
+impl NameMatching {
+    fn as_str(&self) -> &'static str {
+        match self {
+            NameMatching::Bytes => "bytes",
+            NameMatching::Lossy => "lossy",
+        }
+    }
+}
+
+/// How a copied file is checked against its source.
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Verify {
+    /// Trust the checksum computed while copying
+    None,
+    /// Check that the copy has the expected size
+    Size,
+    /// Read the copy back and compare checksums
+    Reread,
+}
+
+impl Verify {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Verify::None => "none",
+            Verify::Size => "size",
+            Verify::Reread => "reread",
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
+pub struct CopyConfig {
+    pub jobs: usize,
+    pub verify: Verify,
+    pub hash: hash::Algorithm,
+    /// Try to share data with the source instead of copying it
+    pub reflink: bool,
+    /// Create directories that hold nothing to back up
+    pub keep_empty_dirs: bool,
+    /// Recreate sockets as placeholder nodes instead of only recording them
+    pub placeholders: bool,
+    /// How many more times a file that changes while it is copied is copied
+    pub retries: u32,
+}
+
+impl Default for CopyConfig {
+    fn default() -> Self {
+        CopyConfig::initialize()
+    }
+}
+
+impl CopyConfig {
+    /// Number of files copied concurrently. 0 means one per CPU.
+    pub fn jobs(&self) -> usize {
+        match self.jobs {
+            0 => std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+            jobs => jobs,
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize)]
@@ -27,6 +159,8 @@ pub struct Config {
     pub path_config: PathConfig,
     pub file_config: FileConfig,
     pub output_config: OutputConfig,
+    #[serde(default)]
+    pub copy_config: CopyConfig,
 }
 
 // In-process retention for the last used config path. This lets commands like
@@ -50,7 +184,7 @@ fn get_current_config_path() -> Option<PathBuf> {
 trait ValidConfig {
     fn initialize() -> Self;
     fn table(&self) -> Table;
-    fn update(&mut self, args: &ConfigArgs) -> Result<(), String>;
+    fn update(&mut self, args: &ConfigArgs) -> Result<(), Error>;
     fn reset(&mut self, args: &ResetArgs);
 }
 
@@ -77,15 +211,15 @@ impl ValidConfig for PathConfig {
         table
     }
 
-    fn update(&mut self, args: &ConfigArgs) -> Result<(), String> {
+    fn update(&mut self, args: &ConfigArgs) -> Result<(), Error> {
         if let Some(path) = args.source_path.as_deref() {
             self.source_path = path.to_string_lossy().to_string();
-            println!("Source directory set to {}", self.source_path);
+            info!("Source directory set to {}", self.source_path);
         }
 
         if let Some(path) = args.dest_path.as_deref() {
             self.dest_path = path.to_string_lossy().to_string();
-            println!("Destination directory set to {}", self.dest_path);
+            info!("Destination directory set to {}", self.dest_path);
         }
         
         Ok(())
@@ -98,7 +232,7 @@ impl ValidConfig for PathConfig {
                 .join(".config/babycancer/source")
                 .to_string_lossy()
                 .to_string();
-            println!("Source directory reset to {}", self.source_path);
+            info!("Source directory reset to {}", self.source_path);
         }
 
         if args.dest_path || args.all {
@@ -107,7 +241,7 @@ impl ValidConfig for PathConfig {
                 .join(".config/babycancer/dest")
                 .to_string_lossy()
                 .to_string();
-            println!("Destination directory set to {}", self.dest_path);
+            info!("Destination directory set to {}", self.dest_path);
         }
     }
 }
@@ -120,6 +254,13 @@ impl ValidConfig for FileConfig {
             date: None,
             size: None,
             user: None,
+            symlinks: Symlinks::Preserve,
+            one_file_system: false,
+            skip_tmpfs: false,
+            max_depth: None,
+            exclude_markers: default_exclude_markers(),
+            ignore_files: false,
+            name_matching: NameMatching::Bytes,
         }
     }
 
@@ -145,25 +286,41 @@ impl ValidConfig for FileConfig {
             Some(user) => Item::Value(user.clone().into()),
             None => Item::None,
         };
+        table["symlinks"] = Item::Value(self.symlinks.as_str().into());
+        table["one_file_system"] = Item::Value(self.one_file_system.into());
+        table["skip_tmpfs"] = Item::Value(self.skip_tmpfs.into());
+        table["max_depth"] = match self.max_depth {
+            Some(max_depth) => Item::Value((max_depth as i64).into()),
+            None => Item::None,
+        };
+        let markers: toml_edit::Array = self.exclude_markers.iter().map(String::as_str).collect();
+        table["exclude_markers"] = Item::Value(markers.into());
+        table["ignore_files"] = Item::Value(self.ignore_files.into());
+        table["name_matching"] = Item::Value(self.name_matching.as_str().into());
         table
     }
 
-    fn update(&mut self, args: &ConfigArgs) -> Result<(), String> {
+    fn update(&mut self, args: &ConfigArgs) -> Result<(), Error> {
         if let Some(path) = args.file_path.as_deref() {
             self.file_path = Some(path.to_string());
-            println!(
+            info!(
                 "File path set to {}",
                 self.file_path.as_ref().unwrap()
             );
         }
 
         if let Some(name) = args.file_name.as_deref() {
-            // Validate regex pattern before setting
-            if let Err(e) = Regex::new(name) {
-                return Err(format!("Invalid regex pattern '{}': {}", name, e));
+            // Validate regex pattern before setting. Byte patterns accept
+            // everything text patterns do, and the backup checks it again
+            // against the name matching in use.
+            if let Err(e) = regex::bytes::Regex::new(name) {
+                return Err(Error::Filter(format!(
+                    "invalid regex pattern '{}': {}",
+                    name, e
+                )));
             }
             self.file_name = Some(name.to_owned());
-            println!(
+            info!(
                 "File name set to {}",
                 self.file_name.as_ref().unwrap()
             );
@@ -171,17 +328,52 @@ impl ValidConfig for FileConfig {
 
         if let Some(date) = args.date.as_deref() {
             self.date = Some(date.to_owned());
-            println!("Date set to {}", self.date.as_ref().unwrap());
+            info!("Date set to {}", self.date.as_ref().unwrap());
         }
 
         if let Some(size) = args.size {
             self.size = Some(size);
-            println!("Size set to {}", self.size.unwrap());
+            info!("Size set to {}", self.size.unwrap());
         }
 
         if let Some(user) = args.user.as_deref() {
             self.user = Some(user.to_owned());
-            println!("User set to {}", self.user.as_ref().unwrap());
+            info!("User set to {}", self.user.as_ref().unwrap());
+        }
+
+        if let Some(symlinks) = args.symlinks {
+            self.symlinks = symlinks;
+            info!("Symlinks policy set to {}", self.symlinks.as_str());
+        }
+
+        if let Some(one_file_system) = args.one_file_system {
+            self.one_file_system = one_file_system;
+            info!("Stay on one file system: {}", self.one_file_system);
+        }
+
+        if let Some(skip_tmpfs) = args.skip_tmpfs {
+            self.skip_tmpfs = skip_tmpfs;
+            info!("Skip tmpfs mounts: {}", self.skip_tmpfs);
+        }
+
+        if let Some(max_depth) = args.max_depth {
+            self.max_depth = Some(max_depth);
+            info!("Maximum depth set to {}", max_depth);
+        }
+
+        if let Some(markers) = &args.exclude_markers {
+            self.exclude_markers = markers.iter().filter(|m| !m.is_empty()).cloned().collect();
+            info!("Exclusion markers set to {:?}", self.exclude_markers);
+        }
+
+        if let Some(ignore_files) = args.ignore_files {
+            self.ignore_files = ignore_files;
+            info!("Use ignore files: {}", self.ignore_files);
+        }
+
+        if let Some(name_matching) = args.name_matching {
+            self.name_matching = name_matching;
+            info!("File names matched as {}", self.name_matching.as_str());
         }
         
         Ok(())
@@ -190,36 +382,72 @@ impl ValidConfig for FileConfig {
     fn reset(&mut self, args: &ResetArgs) {
         if args.file_path || args.all {
             self.file_path = None;
-            println!("File path reset");
+            info!("File path reset");
         }
 
         if args.file_name || args.all {
             self.file_name = None;
-            println!("File name reset");
+            info!("File name reset");
         }
 
         if args.date || args.all {
             self.date = None;
-            println!("Date reset");
+            info!("Date reset");
         }
 
         if args.size || args.all {
             self.size = None;
-            println!("Size reset");
+            info!("Size reset");
         }
 
         if args.user || args.all {
             self.user = None;
-            println!("User reset");
+            info!("User reset");
+        }
+
+        if args.symlinks || args.all {
+            self.symlinks = Symlinks::Preserve;
+            info!("Symlinks policy reset");
+        }
+
+        if args.one_file_system || args.all {
+            self.one_file_system = false;
+            info!("Stay on one file system reset");
+        }
+
+        if args.skip_tmpfs || args.all {
+            self.skip_tmpfs = false;
+            info!("Skip tmpfs mounts reset");
+        }
+
+        if args.max_depth || args.all {
+            self.max_depth = None;
+            info!("Maximum depth reset");
+        }
+
+        if args.exclude_markers || args.all {
+            self.exclude_markers = default_exclude_markers();
+            info!("Exclusion markers reset");
+        }
+
+        if args.ignore_files || args.all {
+            self.ignore_files = false;
+            info!("Use ignore files reset");
+        }
+
+        if args.name_matching || args.all {
+            self.name_matching = NameMatching::Bytes;
+            info!("File name matching reset");
         }
     }
 }
 
 impl ValidConfig for OutputConfig {
     fn initialize() -> Self {
-        OutputConfig { 
+        OutputConfig {
             tar: false,
             gzip: false,
+            report: false,
         }
     }
 
@@ -227,32 +455,145 @@ impl ValidConfig for OutputConfig {
         let mut table = Table::new();
         table["tar"] = Item::Value(self.tar.into());
         table["gzip"] = Item::Value(self.gzip.into());
+        table["report"] = Item::Value(self.report.into());
         table
     }
     
-    fn update(&mut self, args: &ConfigArgs) -> Result<(), String> {
+    fn update(&mut self, args: &ConfigArgs) -> Result<(), Error> {
         if let Some(tar) = args.tar {
             self.tar = tar;
-            println!("Use tar for backup: {}", self.tar);
+            info!("Use tar for backup: {}", self.tar);
         }
         
         if let Some(gzip) = args.gzip {
             self.gzip = gzip;
-            println!("Use gzip compression: {}", self.gzip);
+            info!("Use gzip compression: {}", self.gzip);
         }
-        
+
+        if let Some(report) = args.report {
+            self.report = report;
+            info!("Save backup report: {}", self.report);
+        }
+
         Ok(())
     }
 
     fn reset(&mut self, args: &ResetArgs) {
         if args.tar || args.all {
             self.tar = false;
-            println!("Use tar reset");
+            info!("Use tar reset");
         }
         
         if args.gzip || args.all {
             self.gzip = false;
-            println!("Use gzip reset");
+            info!("Use gzip reset");
+        }
+
+        if args.report || args.all {
+            self.report = false;
+            info!("Save backup report reset");
+        }
+    }
+}
+
+impl ValidConfig for CopyConfig {
+    fn initialize() -> Self {
+        CopyConfig {
+            jobs: 1,
+            verify: Verify::Reread,
+            hash: hash::Algorithm::Crc32,
+            reflink: false,
+            keep_empty_dirs: false,
+            placeholders: false,
+            retries: 3,
+        }
+    }
+
+    fn table(&self) -> Table {
+        let mut table = Table::new();
+        table["jobs"] = Item::Value((self.jobs as i64).into());
+        table["verify"] = Item::Value(self.verify.as_str().into());
+        table["hash"] = Item::Value(self.hash.as_str().into());
+        table["reflink"] = Item::Value(self.reflink.into());
+        table["keep_empty_dirs"] = Item::Value(self.keep_empty_dirs.into());
+        table["placeholders"] = Item::Value(self.placeholders.into());
+        table["retries"] = Item::Value((self.retries as i64).into());
+        table
+    }
+
+    fn update(&mut self, args: &ConfigArgs) -> Result<(), Error> {
+        if let Some(jobs) = args.jobs {
+            self.jobs = jobs;
+            info!("Parallel copy jobs set to {}", self.jobs);
+        }
+
+        if let Some(verify) = args.verify {
+            self.verify = verify;
+            info!("Verify copies: {}", self.verify.as_str());
+        }
+
+        if let Some(hash) = args.hash {
+            self.hash = hash;
+            info!("Hash algorithm set to {}", self.hash.as_str());
+        }
+
+        if let Some(reflink) = args.reflink {
+            self.reflink = reflink;
+            info!("Use reflink copies: {}", self.reflink);
+        }
+
+        if let Some(keep_empty_dirs) = args.keep_empty_dirs {
+            self.keep_empty_dirs = keep_empty_dirs;
+            info!("Keep empty directories: {}", self.keep_empty_dirs);
+        }
+
+        if let Some(placeholders) = args.placeholders {
+            self.placeholders = placeholders;
+            info!("Recreate sockets as placeholders: {}", self.placeholders);
+        }
+
+        if let Some(retries) = args.retries {
+            self.retries = retries;
+            info!("Copy retries set to {}", self.retries);
+        }
+
+        Ok(())
+    }
+
+    fn reset(&mut self, args: &ResetArgs) {
+        if args.jobs || args.all {
+            self.jobs = 1;
+            info!("Parallel copy jobs reset");
+        }
+
+        if args.verify || args.all {
+            self.verify = Verify::Reread;
+            info!("Verify copies reset");
+        }
+
+        if args.hash || args.all {
+            self.hash = hash::Algorithm::Crc32;
+            info!("Hash algorithm reset");
+        }
+
+        if args.reflink || args.all {
+            self.reflink = false;
+            info!("Reflink copies reset");
+        }
+
+        if args.keep_empty_dirs || args.all {
+            self.keep_empty_dirs = false;
+            info!("Keep empty directories reset");
+        }
+
+        if args.placeholders || args.all {
+            self.placeholders = false;
+            info!("Socket placeholders reset");
+        }
+
+        if args.retries || args.all {
+            self.retries = 3;
+            info!("Copy retries reset");
         }
     }
 }
@@ -291,6 +632,34 @@ pub struct ConfigArgs {
     #[arg(short, long, value_name = "USER")]
     user: Option<String>,
 
+    /// What to do with symbolic links
+    #[arg(long, value_enum, value_name = "POLICY")]
+    symlinks: Option<Symlinks>,
+
+    /// Do not descend into directories on other file systems
+    #[arg(long)]
+    one_file_system: Option<bool>,
+
+    /// Skip tmpfs mounts
+    #[arg(long)]
+    skip_tmpfs: Option<bool>,
+
+    /// Only back up this many directory levels below the source
+    #[arg(long, value_name = "DEPTH")]
+    max_depth: Option<usize>,
+
+    /// Comma-separated names of files that exclude the directory holding them
+    #[arg(long, value_name = "NAMES", value_delimiter = ',')]
+    exclude_markers: Option<Vec<String>>,
+
+    /// Skip what .gitignore, .ignore and .babycancerignore files match
+    #[arg(long)]
+    ignore_files: Option<bool>,
+
+    /// How the file name pattern matches names that are not valid UTF-8
+    #[arg(long, value_enum, value_name = "MODE")]
+    name_matching: Option<NameMatching>,
+
     /// Use tar for backup
     #[arg(short, long)]
     tar: Option<bool>,
@@ -299,6 +668,38 @@ pub struct ConfigArgs {
     #[arg(short, long)]
     gzip: Option<bool>,
 
+    /// Save a JSON report of each backup run in the destination
+    #[arg(long)]
+    report: Option<bool>,
+
+    /// Number of files to copy in parallel (0 for one per CPU)
+    #[arg(short, long, value_name = "N")]
+    jobs: Option<usize>,
+
+    /// How to verify copied files
+    #[arg(long, value_enum, value_name = "MODE")]
+    verify: Option<Verify>,
+
+    /// Hash algorithm used to check file integrity
+    #[arg(long, value_enum, value_name = "ALGORITHM")]
+    hash: Option<hash::Algorithm>,
+
+    /// Clone files with copy-on-write reflinks when the file system supports it
+    #[arg(long)]
+    reflink: Option<bool>,
+
+    /// Create directories in the backup even when nothing in them is backed up
+    #[arg(long)]
+    keep_empty_dirs: Option<bool>,
+
+    /// Recreate sockets as placeholder nodes in plain copies
+    #[arg(long)]
+    placeholders: Option<bool>,
+
+    /// How many times to copy again a file that changes while it is copied
+    #[arg(long, value_name = "COUNT")]
+    retries: Option<u32>,
+
     /// Output config file content
     #[arg(short, long)]
     output: bool,
@@ -338,6 +739,34 @@ pub struct ResetArgs {
     #[arg(short, long)]
     user: bool,
 
+    /// Reset the symlinks policy
+    #[arg(long)]
+    symlinks: bool,
+
+    /// Reset staying on one file system
+    #[arg(long)]
+    one_file_system: bool,
+
+    /// Reset skipping tmpfs mounts
+    #[arg(long)]
+    skip_tmpfs: bool,
+
+    /// Reset the maximum depth
+    #[arg(long)]
+    max_depth: bool,
+
+    /// Reset the exclusion markers
+    #[arg(long)]
+    exclude_markers: bool,
+
+    /// Reset using ignore files
+    #[arg(long)]
+    ignore_files: bool,
+
+    /// Reset file name matching
+    #[arg(long)]
+    name_matching: bool,
+
     /// Use tar for backup
     #[arg(short, long)]
     tar: bool,
@@ -346,40 +775,75 @@ pub struct ResetArgs {
     #[arg(short, long)]
     gzip: bool,
 
+    /// Save a JSON report of each backup run in the destination
+    #[arg(long)]
+    report: bool,
+
+    /// Reset the number of files to copy in parallel
+    #[arg(short, long)]
+    jobs: bool,
+
+    /// Reset how to verify copied files
+    #[arg(long)]
+    verify: bool,
+
+    /// Reset the hash algorithm
+    #[arg(long)]
+    hash: bool,
+
+    /// Reset reflink copies
+    #[arg(long)]
+    reflink: bool,
+
+    /// Reset keeping empty directories
+    #[arg(long)]
+    keep_empty_dirs: bool,
+
+    /// Reset socket placeholders
+    #[arg(long)]
+    placeholders: bool,
+
+    /// Reset copy retries
+    #[arg(long)]
+    retries: bool,
+
     /// Reset all configurations
     #[arg(short, long)]
     all: bool,
 }
 
-fn initialize_config(path: &Path) {
-    let _ = std::fs::create_dir_all(path.parent().unwrap()).map_err(|err| {
-        eprintln!(
-            "Cannot create directory {}: {}",
-            path.parent().unwrap().display(),
+fn initialize_config(path: &Path) -> Result<(), Error> {
+    let parent = path.parent().unwrap();
+    std::fs::create_dir_all(parent)
+        .map_err(|err| {
+            error!("Cannot create directory {}: {}", parent.display(), err);
             err
-        );
-        err
-    });
+        })
+        .at(parent)?;
 
-    let _ = File::create(&path).map_err(|err| {
-        eprintln!("Cannot create {}: {}", path.display(), err);
-        err
-    });
+    File::create(path)
+        .map_err(|err| {
+            error!("Cannot create {}: {}", path.display(), err);
+            err
+        })
+        .at(path)?;
 
     let path_config = PathConfig::initialize();
     let file_config = FileConfig::initialize();
     let output_config = OutputConfig::initialize();
+    let copy_config = CopyConfig::initialize();
 
     let config = Config {
         path_config,
         file_config,
         output_config,
+        copy_config,
     };
 
-    update_config_file(path, &config);
+    update_config_file(path, &config)
 }
 
-fn check_config_file(config_path: &Option<PathBuf>) -> Result<PathBuf, std::io::Error> {
+fn check_config_file(config_path: &Option<PathBuf>) -> Result<PathBuf, Error> {
     // Precedence:
     // 1) explicit path from args
     // 2) last path set in this process via set_current_config_path
@@ -393,8 +857,8 @@ fn check_config_file(config_path: &Option<PathBuf>) -> Result<PathBuf, std::io::
         match OpenOptions::new().read(true).write(true).open(&path) {
             Ok(_) => return Ok(path),
             Err(err) => {
-                eprintln!("Cannot open {}: {}", path.display(), err);
-                return Err(err);
+                error!("Cannot open {}: {}", path.display(), err);
+                return Err(err).at(&path);
             }
         }
     }
@@ -405,102 +869,93 @@ fn check_config_file(config_path: &Option<PathBuf>) -> Result<PathBuf, std::io::
         .join(".config/rustbackup/config.toml");
 
     match OpenOptions::new().read(true).write(true).open(path.clone()) {
-        Ok(_) => {
-            Ok(path)
-        }
+        Ok(_) => Ok(path),
         Err(_) => {
             // Config file does not exist, create directories and initialize config
-            initialize_config(&path);
-            println!("Configuration file created at {}", path.display());
+            initialize_config(&path)?;
+            info!("Configuration file created at {}", path.display());
             Ok(path)
         }
     }
 }
 
-fn update_config(config: &mut Config, args: &ConfigArgs) -> Result<(), String> {
+fn update_config(config: &mut Config, args: &ConfigArgs) -> Result<(), Error> {
     let path_config = &mut config.path_config;
     let file_config = &mut config.file_config;
     let output_config = &mut config.output_config;
+    let copy_config = &mut config.copy_config;
 
     path_config.update(args)?;
     file_config.update(args)?;
     output_config.update(args)?;
+    copy_config.update(args)?;
     
     Ok(())
 }
 
-fn update_config_file(path: &Path, config: &Config) {
+fn update_config_file(path: &Path, config: &Config) -> Result<(), Error> {
     let mut file = OpenOptions::new()
         .read(true)
         .write(true)
         .open(path)
         .map_err(|err| {
-            eprintln!("Cannot open {}: {}", path.display(), err);
+            error!("Cannot open {}: {}", path.display(), err);
             err
         })
-        .unwrap();
+        .at(path)?;
 
-    file.set_len(0).unwrap();
+    file.set_len(0).at(path)?;
 
     let path_config = &config.path_config;
     let file_config = &config.file_config;
     let output_config = &config.output_config;
+    let copy_config = &config.copy_config;
 
     let mut doc: DocumentMut = "".to_string().parse::<DocumentMut>().unwrap();
 
     doc["path_config"] = Item::Table(path_config.table());
     doc["file_config"] = Item::Table(file_config.table());
     doc["output_config"] = Item::Table(output_config.table());
+    doc["copy_config"] = Item::Table(copy_config.table());
 
-    file.write_all(doc.to_string().as_bytes()).unwrap();
+    file.write_all(doc.to_string().as_bytes()).at(path)
 }
 
-fn read_config(path: &PathBuf) -> Result<Config, std::io::Error> {
-    let content = fs::read_to_string(path).map_err(|err| {
-        eprintln!("Failed to read {}: {}", path.display(), err);
-        err
-    })?;
-    println!("Configuration file read at {}", path.display());
+fn read_config(path: &Path) -> Result<Config, Error> {
+    let content = fs::read_to_string(path)
+        .map_err(|err| {
+            error!("Failed to read {}: {}", path.display(), err);
+            err
+        })
+        .at(path)?;
+    debug!("Configuration file read at {}", path.display());
 
     let config: Config = toml::de::from_str(&content).map_err(|err| {
-        eprintln!("Failed to parse config {:?}: {}", content, err);
-        std::io::Error::new(std::io::ErrorKind::InvalidData, err)
+        error!("Failed to parse config {:?}: {}", content, err);
+        Error::Config(format!("{}: {}", path.display(), err))
     })?;
 
     Ok(config)
 }
 
-fn print_config(path: &PathBuf) -> Result<(), std::io::Error> {
-    let content = fs::read_to_string(path).map_err(|err| {
-        eprintln!("Failed to read {}: {}", path.display(), err);
-        err
-    })?;
-
-    println!(
-        "Configuration file read at {}:\n{}",
-        path.display(),
-        content
-    );
-
-    Ok(())
-}
-
 fn reset_config(config: &mut Config, args: &ResetArgs) {
     let path_config = &mut config.path_config;
     let file_config = &mut config.file_config;
     let output_config = &mut config.output_config;
+    let copy_config = &mut config.copy_config;
 
     path_config.reset(args);
     file_config.reset(args);
     output_config.reset(args);
+    copy_config.reset(args);
 }
 
-pub fn get_config(config_path: &Option<PathBuf>) -> Result<Config, std::io::Error> {
-    let mut config_file = check_config_file(config_path)?;
-    read_config(&mut config_file)
+pub fn get_config(config_path: &Option<PathBuf>) -> Result<Config, Error> {
+    let config_file = check_config_file(config_path)?;
+    read_config(&config_file)
 }
 
-pub fn command_config(args: &ConfigArgs) -> Result<(), std::io::Error> {
+pub fn command_config(args: &ConfigArgs) -> Result<(), Error> {
     let config_path = check_config_file(&args.config_path)?;
 
     // If the user explicitly provided a config path, retain it for this process.
@@ -510,21 +965,24 @@ pub fn command_config(args: &ConfigArgs) -> Result<(), std::io::Error> {
 
     let mut config = read_config(&config_path)?;
 
-    if let Err(e) = update_config(&mut config, &args) {
-        eprintln!("Configuration error: {}", e);
-        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, e));
+    if let Err(e) = update_config(&mut config, args) {
+        error!("Configuration error: {}", e);
+        return Err(e);
     }
 
-    update_config_file(&config_path, &config);
+    update_config_file(&config_path, &config)?;
 
     if args.output {
-        print_config(&config_path)?;
+        emit(Event::Config {
+            path: &config_path,
+            config: &config,
+        });
     }
 
     Ok(())
 }
 
-pub fn command_reset(args: &ResetArgs) -> Result<(), std::io::Error> {
+pub fn command_reset(args: &ResetArgs) -> Result<(), Error> {
     let config_path = check_config_file(&args.config_path)?;
 
     // If the user explicitly provided a config path, retain it for this process.
@@ -534,11 +992,14 @@ pub fn command_reset(args: &ResetArgs) -> Result<(), std::io::Error> {
 
     let mut config = read_config(&config_path)?;
 
-    reset_config(&mut config, &args);
+    reset_config(&mut config, args);
 
-    update_config_file(&config_path, &config);
+    update_config_file(&config_path, &config)?;
 
-    println!("Configuration file reset at {}", config_path.display());
+    emit(Event::Reset {
+        path: &config_path,
+        config: &config,
+    });
 
     Ok(())
 }
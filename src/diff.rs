@@ -0,0 +1,196 @@
+use crate::*;
+use log::info;
+use manifest::{MANIFEST_FILE_NAME, Manifest};
+use output::{Event, emit};
+use report::Report;
+use std::collections::BTreeMap;
+use walk::{Entry, Kind};
+
+#[derive(Parser)]
+pub struct DiffArgs {
+    /// Set a custom config file
+    #[arg(short, long, value_name = "FILE")]
+    config_path: Option<PathBuf>,
+
+    /// Compare file contents even when size and modification time match
+    #[arg(long)]
+    checksum: bool,
+}
+
+/// What the last backup holds for one path.
+struct Known {
+    kind: Kind,
+    size: u64,
+    /// Modification time in seconds and nanoseconds, only known when the
+    /// backup has a manifest
+    mtime: Option<(i64, i64)>,
+    hash: Option<String>,
+}
+
+#[derive(Default)]
+struct Counts {
+    added: usize,
+    modified: usize,
+    removed: usize,
+    type_changed: usize,
+    unchanged: usize,
+    unreadable: usize,
+}
+
+/// Read what the last backup holds, from its manifest if it has one, or by
+/// scanning the destination otherwise. Returns the entries and the hash
+/// algorithm their digests use.
+fn load_backup(
+    dest_path: &Path,
+    config: &Config,
+) -> Result<(BTreeMap<PathBuf, Known>, hash::Algorithm), Error> {
+    if dest_path.join(MANIFEST_FILE_NAME).exists() {
+        let manifest = Manifest::load(dest_path)?;
+        let known = manifest
+            .entries
+            .into_iter()
+            .map(|entry| {
+                let known = Known {
+                    kind: entry.kind,
+                    size: entry.size,
+                    mtime: Some((entry.mtime, entry.mtime_nsec)),
+                    hash: entry.hash,
+                };
+                (entry.path, known)
+            })
+            .collect();
+        return Ok((known, manifest.algorithm));
+    }
+
+    let algorithm = config.copy_config.hash;
+    if !dest_path.is_dir() {
+        return Ok((BTreeMap::new(), algorithm));
+    }
+    info!(
+        "No manifest found in {}, scanning it instead.",
+        dest_path.display()
+    );
+
+    let output_config = &config.output_config;
+    let archive = match (output_config.tar, output_config.gzip) {
+        (false, _) => None,
+        (true, false) => Some(Path::new("backup.tar")),
+        (true, true) => Some(Path::new("backup.tar.gz")),
+    };
+    let known = verify::scan_backup(dest_path, archive, algorithm)?
+        .into_iter()
+        .map(|(path, found)| {
+            let known = Known {
+                kind: found.kind,
+                size: found.size,
+                mtime: None,
+                hash: found.hash,
+            };
+            (path, known)
+        })
+        .collect();
+    Ok((known, algorithm))
+}
+
+/// Describe how a source file differs from its backed-up copy, if it does.
+fn compare_file(
+    entry: &Entry,
+    known: &Known,
+    algorithm: hash::Algorithm,
+    checksum: bool,
+) -> Result<Option<String>, Error> {
+    if entry.size() != known.size {
+        return Ok(Some(format!("size {} -> {}", known.size, entry.size())));
+    }
+
+    let mtime = (entry.metadata.mtime(), entry.metadata.mtime_nsec());
+    let touched = known.mtime != Some(mtime);
+    if !touched && !checksum {
+        return Ok(None);
+    }
+    match &known.hash {
+        Some(hash) => {
+            let current = hash::hash_file(&entry.path, algorithm).at(&entry.path)?;
+            Ok((current != *hash).then(|| "contents".to_string()))
+        }
+        None => Ok(touched.then(|| "modification time".to_string())),
+    }
+}
+
+fn change(path: &Path, change: &str, detail: Option<String>, counter: &mut usize) {
+    *counter += 1;
+    emit(Event::DiffEntry {
+        path,
+        change,
+        detail,
+    });
+}
+
+pub fn command_diff(args: &DiffArgs) -> Result<(), Error> {
+    let config = config::get_config(&args.config_path)?;
+    let source_path = backup::get_source_directory(&config)?;
+    let dest_path = Path::new(&config.path_config.dest_path);
+
+    let (mut known, algorithm) = load_backup(dest_path, &config)?;
+    let mut report = Report::new(&source_path, dest_path);
+    report.silence_failures();
+    let filtered = !config.output_config.tar;
+    let walk = walk::walk(&source_path, &config.file_config, filtered, &mut report)?;
+
+    // Entries the walk could not read are listed, and left out of the rest
+    let mut counts = Counts::default();
+    let mut unreadable = Vec::new();
+    for failure in &report.failures {
+        let path = failure.path.strip_prefix(&source_path).unwrap_or(&failure.path);
+        let detail = Some(failure.reason.clone());
+        change(path, "unreadable", detail, &mut counts.unreadable);
+        unreadable.push(path);
+    }
+
+    // Directories are only containers; their contents are compared one by one.
+    for entry in walk.entries.iter().filter(|e| e.kind != Kind::Dir) {
+        let path = entry.relative.as_path();
+        let Some(backed_up) = known.remove(path) else {
+            change(path, "added", None, &mut counts.added);
+            continue;
+        };
+
+        if backed_up.kind != entry.kind {
+            let detail = format!("{} -> {}", backed_up.kind.as_str(), entry.kind.as_str());
+            change(path, "type_changed", Some(detail), &mut counts.type_changed);
+        } else if entry.kind == Kind::File {
+            match compare_file(entry, &backed_up, algorithm, args.checksum) {
+                Ok(Some(detail)) => change(path, "modified", Some(detail), &mut counts.modified),
+                Ok(None) => counts.unchanged += 1,
+                Err(e) => {
+                    let detail = Some(e.reason());
+                    change(path, "unreadable", detail, &mut counts.unreadable);
+                }
+            }
+        } else {
+            counts.unchanged += 1;
+        }
+    }
+
+    // What is below an unreadable directory may still be there
+    for (path, _) in known.iter().filter(|(path, k)| {
+        k.kind != Kind::Dir && !unreadable.iter().any(|dir| path.starts_with(dir))
+    }) {
+        change(path, "removed", None, &mut counts.removed);
+    }
+
+    emit(Event::DiffSummary {
+        added: counts.added,
+        modified: counts.modified,
+        removed: counts.removed,
+        type_changed: counts.type_changed,
+        unchanged: counts.unchanged,
+        unreadable: counts.unreadable,
+    });
+    if counts.unreadable > 0 {
+        return Err(Error::Incomplete {
+            unreadable: counts.unreadable,
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,109 @@
+use crate::*;
+
+/// Process exit code: everything was backed up.
+pub const EXIT_SUCCESS: i32 = 0;
+/// Process exit code: the command failed and nothing trustworthy was produced.
+pub const EXIT_FAILURE: i32 = 1;
+/// Process exit code: the command line could not be parsed.
+pub const EXIT_USAGE: i32 = 2;
+/// Process exit code: the backup or diff finished but some files were skipped because they failed.
+pub const EXIT_PARTIAL: i32 = 3;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The command line could not be parsed
+    Usage(clap::Error),
+    /// The configuration file is invalid
+    Config(String),
+    /// A file filter cannot be applied
+    Filter(String),
+    /// An IO operation failed on a path
+    Io { path: PathBuf, source: io::Error },
+    /// A copied file does not match its source
+    Integrity { path: PathBuf },
+    /// An archive could not be written
+    Archive { path: PathBuf, source: io::Error },
+    /// The backup finished, but some files could not be backed up
+    Partial { failed: usize },
+    /// The backup does not match its manifest
+    Verification { problems: usize },
+    /// The diff finished, but some files could not be compared
+    Incomplete { unreadable: usize },
+}
+
+impl Error {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Usage(_) => EXIT_USAGE,
+            Error::Partial { .. } | Error::Incomplete { .. } => EXIT_PARTIAL,
+            _ => EXIT_FAILURE,
+        }
+    }
+
+    /// The path the error is about, if it is about one.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Error::Io { path, .. } | Error::Integrity { path } | Error::Archive { path, .. } => {
+                Some(path)
+            }
+            _ => None,
+        }
+    }
+
+    /// What went wrong, without the path, for messages that already name it.
+    pub fn reason(&self) -> String {
+        match self {
+            Error::Io { source, .. } | Error::Archive { source, .. } => source.to_string(),
+            Error::Integrity { .. } => "Checksum mismatch - backup may be corrupted".to_string(),
+            _ => self.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Usage(e) => write!(f, "{}", e),
+            Error::Config(msg) => write!(f, "Invalid configuration: {}", msg),
+            Error::Filter(msg) => write!(f, "Invalid filter: {}", msg),
+            Error::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Integrity { path } => {
+                write!(f, "Checksum mismatch for {} - backup may be corrupted", path.display())
+            }
+            Error::Archive { path, source } => {
+                write!(f, "Failed to write archive {}: {}", path.display(), source)
+            }
+            Error::Partial { failed } => write!(f, "{} file(s) could not be backed up", failed),
+            Error::Verification { problems } => {
+                write!(f, "{} file(s) are missing, extra or corrupted in the backup", problems)
+            }
+            Error::Incomplete { unreadable } => {
+                write!(f, "{} file(s) could not be read and were not compared", unreadable)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Usage(e) => Some(e),
+            Error::Io { source, .. } | Error::Archive { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Attach the path an IO operation was working on to its error.
+pub trait IoContext<T> {
+    fn at(self, path: &Path) -> Result<T, Error>;
+}
+
+impl<T> IoContext<T> for Result<T, io::Error> {
+    fn at(self, path: &Path) -> Result<T, Error> {
+        self.map_err(|source| Error::Io {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+}
@@ -0,0 +1,136 @@
+use crate::*;
+use sha2::Digest;
+
+/// Size of the buffer used to read and copy file contents.
+pub const BUFFER_SIZE: usize = 128 * 1024;
+
+/// Hash algorithm used to check file integrity.
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Algorithm {
+    /// CRC32, fast but only detects accidental corruption
+    Crc32,
+    /// SHA-256
+    Sha256,
+    /// BLAKE3
+    Blake3,
+    /// 64-bit XXH3, fast non-cryptographic hash
+    Xxh3,
+}
+
+impl Algorithm {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Algorithm::Crc32 => "crc32",
+            Algorithm::Sha256 => "sha256",
+            Algorithm::Blake3 => "blake3",
+            Algorithm::Xxh3 => "xxh3",
+        }
+    }
+}
+
+/// Incremental hasher for any of the supported algorithms.
+pub enum Hasher {
+    Crc32(crc32fast::Hasher),
+    Sha256(sha2::Sha256),
+    Blake3(Box<blake3::Hasher>),
+    Xxh3(Box<xxhash_rust::xxh3::Xxh3>),
+}
+
+impl Hasher {
+    pub fn new(algorithm: Algorithm) -> Self {
+        match algorithm {
+            Algorithm::Crc32 => Hasher::Crc32(crc32fast::Hasher::new()),
+            Algorithm::Sha256 => Hasher::Sha256(sha2::Sha256::new()),
+            Algorithm::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+            Algorithm::Xxh3 => Hasher::Xxh3(Box::new(xxhash_rust::xxh3::Xxh3::new())),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Crc32(hasher) => hasher.update(data),
+            Hasher::Sha256(hasher) => hasher.update(data),
+            Hasher::Blake3(hasher) => {
+                hasher.update(data);
+            }
+            Hasher::Xxh3(hasher) => hasher.update(data),
+        }
+    }
+
+    /// Hash `count` zero bytes, as read from a hole in a sparse file.
+    pub fn update_zeros(&mut self, mut count: u64) {
+        let zeros = [0; 64 * 1024];
+        while count > 0 {
+            let chunk = count.min(zeros.len() as u64) as usize;
+            self.update(&zeros[..chunk]);
+            count -= chunk as u64;
+        }
+    }
+
+    /// Return the digest as a lowercase hex string.
+    pub fn finalize(self) -> String {
+        match self {
+            Hasher::Crc32(hasher) => format!("{:08x}", hasher.finalize()),
+            Hasher::Sha256(hasher) => to_hex(&hasher.finalize()),
+            Hasher::Blake3(hasher) => hasher.finalize().to_hex().to_string(),
+            Hasher::Xxh3(hasher) => format!("{:016x}", hasher.digest()),
+        }
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Hash the contents of a file.
+pub fn hash_file(path: &Path, algorithm: Algorithm) -> Result<String, io::Error> {
+    let mut file = File::open(path)?;
+    let mut buffer = vec![0; BUFFER_SIZE];
+    let mut hasher = Hasher::new(algorithm);
+
+    loop {
+        let bytes_read = file.read(&mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..bytes_read]);
+    }
+
+    Ok(hasher.finalize())
+}
+
+/// Reader that hashes everything read through it.
+pub struct HashingReader<R> {
+    inner: R,
+    hasher: Hasher,
+}
+
+impl<R: Read> HashingReader<R> {
+    pub fn new(inner: R, algorithm: Algorithm) -> Self {
+        HashingReader {
+            inner,
+            hasher: Hasher::new(algorithm),
+        }
+    }
+
+    /// Return the digest of the data read so far as a lowercase hex string.
+    pub fn finalize(self) -> String {
+        self.hasher.finalize()
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let bytes_read = self.inner.read(buf)?;
+        self.hasher.update(&buf[..bytes_read]);
+        Ok(bytes_read)
+    }
+}
+
+/// Hash everything readable from `reader`.
+pub fn hash_reader<R: Read>(reader: R, algorithm: Algorithm) -> Result<String, io::Error> {
+    let mut reader = HashingReader::new(reader, algorithm);
+    io::copy(&mut reader, &mut io::sink())?;
+    Ok(reader.finalize())
+}
@@ -0,0 +1,182 @@
+use crate::*;
+use log::warn;
+use regex::bytes::Regex;
+use std::os::unix::ffi::OsStrExt;
+
+/// Names of the ignore files read in each directory, lowest precedence first.
+pub const IGNORE_FILE_NAMES: &[&str] = &[".gitignore", ".ignore", ".babycancerignore"];
+
+/// One line of an ignore file.
+struct Rule {
+    regex: Regex,
+    negated: bool,
+    dir_only: bool,
+}
+
+/// The patterns of the ignore files found in one directory, which apply to
+/// everything below it.
+pub struct IgnoreRules {
+    dir_path: PathBuf,
+    rules: Vec<Rule>,
+}
+
+/// Translate a single path component of a glob into a regex.
+fn component_regex(glob: &str) -> String {
+    let chars: Vec<char> = glob.chars().collect();
+    let mut regex = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            '\\' if i + 1 < chars.len() => {
+                i += 1;
+                regex.push_str(&regex::escape(&chars[i].to_string()));
+            }
+            '[' => {
+                // A `]` right after the opening bracket or its negation is
+                // part of the class.
+                let mut end = i + 1;
+                if matches!(chars.get(end), Some('!' | '^')) {
+                    end += 1;
+                }
+                if chars.get(end) == Some(&']') {
+                    end += 1;
+                }
+                match chars[end.min(chars.len())..].iter().position(|&c| c == ']') {
+                    Some(offset) => {
+                        let mut body = &chars[i + 1..end + offset];
+                        regex.push('[');
+                        if let Some(('!' | '^', rest)) = body.split_first() {
+                            regex.push_str("^/");
+                            body = rest;
+                        }
+                        for &c in body {
+                            if c == '-' {
+                                regex.push('-');
+                            } else {
+                                regex.push_str(&regex::escape(&c.to_string()));
+                            }
+                        }
+                        regex.push(']');
+                        i = end + offset;
+                    }
+                    None => regex.push_str(r"\["),
+                }
+            }
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+        i += 1;
+    }
+    regex
+}
+
+/// Parse one line of an ignore file, if it holds a pattern.
+fn parse_rule(line: &str) -> Option<Result<Rule, regex::Error>> {
+    // Trailing spaces are dropped, unless escaped with a backslash
+    let trimmed = line.trim_end_matches(' ');
+    let line = if trimmed.ends_with('\\') && trimmed.len() < line.len() {
+        &line[..trimmed.len() + 1]
+    } else {
+        trimmed
+    };
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let (negated, pattern) = match line.strip_prefix('!') {
+        Some(pattern) => (true, pattern),
+        None => (false, line),
+    };
+    let (dir_only, pattern) = match pattern.strip_suffix('/') {
+        Some(pattern) => (true, pattern),
+        None => (false, pattern),
+    };
+    // A pattern with a slash other than a trailing one is relative to the
+    // directory of the ignore file; any other matches at any depth below it.
+    let anchored = pattern.contains('/');
+    let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+    if pattern.is_empty() {
+        return None;
+    }
+
+    // Names are matched as bytes, so that wildcards also match names that are
+    // not valid UTF-8.
+    let mut regex = String::from(if anchored { "(?s-u)^" } else { "(?s-u)^(?:.*/)?" });
+    let components: Vec<&str> = pattern.split('/').collect();
+    for (index, component) in components.iter().enumerate() {
+        let last = index + 1 == components.len();
+        match (*component, last) {
+            // Everything inside the directory
+            ("**", true) => regex.push_str(".*"),
+            // Any number of directories, including none
+            ("**", false) => regex.push_str("(?:.*/)?"),
+            (component, last) => {
+                regex.push_str(&component_regex(component));
+                if !last {
+                    regex.push('/');
+                }
+            }
+        }
+    }
+    regex.push('$');
+
+    Some(Regex::new(&regex).map(|regex| Rule {
+        regex,
+        negated,
+        dir_only,
+    }))
+}
+
+impl IgnoreRules {
+    /// Read the ignore files of `dir_path`. Returns `None` when it has none.
+    pub fn read(dir_path: &Path) -> Result<Option<IgnoreRules>, Error> {
+        let mut rules = Vec::new();
+        for name in IGNORE_FILE_NAMES {
+            let path = dir_path.join(name);
+            let content = match fs::read(&path) {
+                Ok(content) => content,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e).at(&path),
+            };
+            for line in String::from_utf8_lossy(&content).lines() {
+                match parse_rule(line) {
+                    Some(Ok(rule)) => rules.push(rule),
+                    Some(Err(e)) => {
+                        warn!("Ignoring pattern {:?} in {}: {}", line, path.display(), e)
+                    }
+                    None => {}
+                }
+            }
+        }
+
+        if rules.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(IgnoreRules {
+            dir_path: dir_path.to_path_buf(),
+            rules,
+        }))
+    }
+}
+
+/// Whether `path` is ignored by `levels`, the rules of the directories above
+/// it from the top down. The last matching pattern of the deepest directory
+/// that has one decides, so that a `!` pattern can re-include a path.
+pub fn is_ignored(levels: &[IgnoreRules], path: &Path, is_dir: bool) -> bool {
+    for level in levels.iter().rev() {
+        let Ok(relative) = path.strip_prefix(&level.dir_path) else {
+            continue;
+        };
+        let relative = relative.as_os_str().as_bytes();
+        let matched = level
+            .rules
+            .iter()
+            .rev()
+            .find(|rule| (is_dir || !rule.dir_only) && rule.regex.is_match(relative));
+        if let Some(rule) = matched {
+            return !rule.negated;
+        }
+    }
+    false
+}
@@ -0,0 +1,114 @@
+use crate::*;
+use log::{debug, info, warn};
+use manifest::ManifestEntry;
+use std::collections::HashMap;
+
+/// File name of the journal kept in the destination directory while a backup
+/// runs.
+pub const JOURNAL_FILE_NAME: &str = "backup-journal.jsonl";
+
+/// First line of the journal. Digests of files resumed from the journal are
+/// kept as they are, so they must use the algorithm of the new run.
+#[derive(Deserialize, Serialize)]
+struct Header {
+    algorithm: hash::Algorithm,
+}
+
+/// Append-only record of the entries a running backup has finished, one JSON
+/// line each after a header, so that an interrupted run can be resumed.
+pub struct Journal {
+    path: PathBuf,
+    file: File,
+}
+
+impl Journal {
+    /// Start the journal in `dest_path` for a run hashing files with
+    /// `algorithm`. When `resume` is set, the entries recorded by an
+    /// interrupted run with the same algorithm are kept and returned;
+    /// otherwise the journal starts empty.
+    pub fn open(
+        dest_path: &Path,
+        resume: bool,
+        algorithm: hash::Algorithm,
+    ) -> Result<(Journal, HashMap<PathBuf, ManifestEntry>), Error> {
+        let path = dest_path.join(JOURNAL_FILE_NAME);
+        let mut finished = HashMap::new();
+        let mut kept = false;
+
+        if resume {
+            match fs::read_to_string(&path) {
+                Ok(content) => {
+                    let mut lines = content.lines();
+                    let header = lines.next().and_then(|line| {
+                        serde_json::from_str::<Header>(line)
+                            .map_err(|e| debug!("Invalid journal header {:?}: {}", line, e))
+                            .ok()
+                    });
+                    match header {
+                        Some(header) if header.algorithm == algorithm => kept = true,
+                        Some(header) => info!(
+                            "The interrupted backup used {} rather than {}, starting over.",
+                            header.algorithm.as_str(),
+                            algorithm.as_str()
+                        ),
+                        None => info!("The journal has no valid header, starting over."),
+                    }
+                    // The last line may be torn if the run was killed while
+                    // writing it; that entry is simply backed up again.
+                    for line in lines.filter(|_| kept) {
+                        match serde_json::from_str::<ManifestEntry>(line) {
+                            Ok(entry) => {
+                                finished.insert(entry.path.clone(), entry);
+                            }
+                            Err(e) => debug!("Ignoring journal line {:?}: {}", line, e),
+                        }
+                    }
+                    if kept {
+                        info!(
+                            "Resuming interrupted backup, {} entries already done.",
+                            finished.len()
+                        );
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    info!("No interrupted backup to resume, starting over.");
+                }
+                Err(e) => return Err(e).at(&path),
+            }
+        } else if path.exists() {
+            info!("Discarding the journal of an interrupted backup, use --resume to continue it.");
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(kept)
+            .write(true)
+            .truncate(!kept)
+            .open(&path)
+            .at(&path)?;
+        if !kept {
+            let header = serde_json::to_string(&Header { algorithm }).map_err(io::Error::other);
+            header
+                .and_then(|header| writeln!(file, "{}", header))
+                .at(&path)?;
+        }
+        Ok((Journal { path, file }, finished))
+    }
+
+    /// Record an entry as finished. The journal is only an optimization, so
+    /// failing to write it does not fail the backup.
+    pub fn record(&mut self, entry: &ManifestEntry) {
+        let result = serde_json::to_string(entry)
+            .map_err(io::Error::other)
+            .and_then(|line| writeln!(self.file, "{}", line));
+        if let Err(e) = result {
+            warn!("Failed to write {}: {}", self.path.display(), e);
+        }
+    }
+
+    /// Delete the journal once the backup is complete.
+    pub fn remove(self) -> Result<(), Error> {
+        drop(self.file);
+        fs::remove_file(&self.path).at(&self.path)
+    }
+}
@@ -1,11 +1,27 @@
 pub mod backup;
 pub mod command;
 pub mod config;
+pub mod diff;
+pub mod error;
+pub mod hash;
+pub mod ignore;
+pub mod journal;
+pub mod logger;
+pub mod manifest;
+pub mod output;
+pub mod paths;
+pub mod progress;
 pub mod repl;
+pub mod report;
+pub mod sparse;
+pub mod verify;
+pub mod walk;
+pub mod xattrs;
 
 pub use chrono::NaiveDate;
 pub use clap::{Parser, Subcommand};
 pub use config::Config;
+pub use error::{Error, IoContext};
 pub use nix::sys::stat::{Mode, SFlag, mknod};
 pub use notify::Watcher;
 pub use regex::Regex;
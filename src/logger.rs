@@ -0,0 +1,125 @@
+use crate::*;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::sync::{Mutex, OnceLock};
+
+const LEVELS: [LevelFilter; 6] = [
+    LevelFilter::Off,
+    LevelFilter::Error,
+    LevelFilter::Warn,
+    LevelFilter::Info,
+    LevelFilter::Debug,
+    LevelFilter::Trace,
+];
+
+struct Logger;
+
+static LOGGER: Logger = Logger;
+
+struct Session {
+    level: LevelFilter,
+    log_file: Option<PathBuf>,
+}
+
+// Defaults for every command of this process, taken from the options the
+// program was started with. Options given on a single REPL line override them.
+static SESSION: OnceLock<Mutex<Session>> = OnceLock::new();
+
+// Log file of the command being executed, if any.
+static LOG_FILE: OnceLock<Mutex<Option<File>>> = OnceLock::new();
+
+fn session_cell() -> &'static Mutex<Session> {
+    SESSION.get_or_init(|| {
+        Mutex::new(Session {
+            level: LevelFilter::Info,
+            log_file: None,
+        })
+    })
+}
+
+fn log_file_cell() -> &'static Mutex<Option<File>> {
+    LOG_FILE.get_or_init(|| Mutex::new(None))
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        // Warnings and errors always go to stderr. Everything else goes to
+        // stdout, except in JSON mode where stdout only carries events.
+        if record.level() <= Level::Warn || output::format() == output::Format::Json {
+            let _ = writeln!(io::stderr(), "{}", record.args());
+        } else {
+            let _ = writeln!(io::stdout(), "{}", record.args());
+        }
+
+        if let Some(file) = log_file_cell().lock().unwrap().as_mut() {
+            let _ = writeln!(
+                file,
+                "{} {:<5} {}",
+                chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+                record.level(),
+                record.args()
+            );
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = log_file_cell().lock().unwrap().as_mut() {
+            let _ = file.flush();
+        }
+    }
+}
+
+fn adjust(level: LevelFilter, verbose: u8, quiet: u8) -> LevelFilter {
+    let index = LEVELS.iter().position(|l| *l == level).unwrap() as i32;
+    let index = (index + verbose as i32 - quiet as i32).clamp(1, LEVELS.len() as i32 - 1);
+    LEVELS[index as usize]
+}
+
+fn open_log_file(path: &Path) -> Result<File, Error> {
+    OpenOptions::new().create(true).append(true).open(path).at(path)
+}
+
+/// Set the defaults used by every command of this session.
+pub fn set_defaults(verbose: u8, quiet: u8, log_file: Option<&Path>) -> Result<(), Error> {
+    if let Some(path) = log_file {
+        open_log_file(path)?;
+    }
+
+    let mut session = session_cell().lock().unwrap();
+    session.level = adjust(LevelFilter::Info, verbose, quiet);
+    session.log_file = log_file.map(Path::to_path_buf);
+    Ok(())
+}
+
+/// Install the logger with the session defaults.
+pub fn init() {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(session_cell().lock().unwrap().level);
+    }
+}
+
+/// Configure logging for the command about to run.
+pub fn apply(verbose: u8, quiet: u8, log_file: Option<&Path>) -> Result<(), Error> {
+    init();
+
+    let (level, default_file) = {
+        let session = session_cell().lock().unwrap();
+        (session.level, session.log_file.clone())
+    };
+
+    let file = match log_file.or(default_file.as_deref()) {
+        Some(path) => Some(open_log_file(path)?),
+        None => None,
+    };
+    *log_file_cell().lock().unwrap() = file;
+
+    log::set_max_level(adjust(level, verbose, quiet));
+    Ok(())
+}
@@ -1,5 +1,9 @@
 use babycancer::*;
 
 fn main() {
+    // With arguments, run a single command and report its outcome as the exit code.
+    if std::env::args().len() > 1 {
+        std::process::exit(repl::run_once());
+    }
     repl::run();
 }
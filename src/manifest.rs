@@ -0,0 +1,129 @@
+use crate::*;
+use log::info;
+use walk::{Entry, Kind};
+
+/// File name of the manifest saved in the destination directory.
+pub const MANIFEST_FILE_NAME: &str = "backup-manifest.json";
+
+/// One backed-up entry, with the metadata needed to audit it later.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct ManifestEntry {
+    #[serde(with = "paths")]
+    pub path: PathBuf,
+    pub kind: Kind,
+    pub size: u64,
+    pub mtime: i64,
+    /// Nanoseconds of the modification time, so that a file rewritten within
+    /// the same second is still seen as changed
+    #[serde(default)]
+    pub mtime_nsec: i64,
+    pub mode: u32,
+    pub hash: Option<String>,
+    /// Earlier entry this file is hard-linked to
+    #[serde(default, with = "paths::option")]
+    pub link: Option<PathBuf>,
+    /// Device numbers of a char or block device
+    #[serde(default)]
+    pub device: Option<Device>,
+    /// Nothing was written to the backup for this entry
+    #[serde(default)]
+    pub metadata_only: bool,
+}
+
+/// Major and minor numbers of a device node, stored apart so that reading
+/// them back does not depend on how the platform packs `dev_t`.
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Device {
+    pub major: u64,
+    pub minor: u64,
+}
+
+impl Device {
+    pub fn of(metadata: &fs::Metadata) -> Self {
+        Device {
+            major: nix::sys::stat::major(metadata.rdev()),
+            minor: nix::sys::stat::minor(metadata.rdev()),
+        }
+    }
+
+    pub fn dev(&self) -> nix::sys::stat::dev_t {
+        nix::sys::stat::makedev(self.major, self.minor)
+    }
+}
+
+/// Everything written by one backup run.
+#[derive(Deserialize, Serialize)]
+pub struct Manifest {
+    pub created_at: String,
+    pub algorithm: hash::Algorithm,
+    /// File name of the archive in the destination, for tar backups
+    pub archive: Option<PathBuf>,
+    pub entries: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    pub fn new(algorithm: hash::Algorithm) -> Self {
+        Manifest {
+            created_at: chrono::Local::now().to_rfc3339(),
+            algorithm,
+            archive: None,
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, entry: &Entry, hash: Option<String>) -> &ManifestEntry {
+        self.entries.push(ManifestEntry {
+            path: entry.relative.clone(),
+            kind: entry.kind,
+            size: entry.size(),
+            mtime: entry.metadata.mtime(),
+            mtime_nsec: entry.metadata.mtime_nsec(),
+            mode: entry.metadata.mode(),
+            hash,
+            link: entry.hard_link.clone(),
+            device: matches!(entry.kind, Kind::CharDevice | Kind::BlockDevice)
+                .then(|| Device::of(&entry.metadata)),
+            metadata_only: false,
+        });
+        self.entries.last().unwrap()
+    }
+
+    /// Add an entry of which only the metadata is kept.
+    pub fn add_metadata_only(&mut self, entry: &Entry) -> &ManifestEntry {
+        self.add(entry, None);
+        let added = self.entries.last_mut().unwrap();
+        added.metadata_only = true;
+        added
+    }
+
+    /// Add a hard link, which has the same contents as the entry it links to.
+    pub fn add_link(&mut self, entry: &Entry, target: &Path) -> &ManifestEntry {
+        let hash = self
+            .entries
+            .iter()
+            .rev()
+            .find(|e| e.path == target)
+            .and_then(|e| e.hash.clone());
+        self.add(entry, hash)
+    }
+
+    /// Save the manifest in the destination directory.
+    pub fn save(&self, dest_path: &Path) -> Result<(), Error> {
+        let path = dest_path.join(MANIFEST_FILE_NAME);
+        let content = serde_json::to_string(self)
+            .map_err(io::Error::other)
+            .at(&path)?;
+        backup::write_file(&path, content.as_bytes())?;
+        info!("Manifest saved at {}", path.display());
+        Ok(())
+    }
+
+    /// Load the manifest from the destination directory.
+    pub fn load(dest_path: &Path) -> Result<Manifest, Error> {
+        let path = dest_path.join(MANIFEST_FILE_NAME);
+        let content = fs::read_to_string(&path).at(&path)?;
+        serde_json::from_str(&content)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            .at(&path)
+    }
+}
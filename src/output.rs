@@ -0,0 +1,227 @@
+use crate::*;
+use log::{debug, error, info, trace, warn};
+use report::Report;
+use std::sync::{Mutex, OnceLock};
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// Human-readable text
+    #[default]
+    Text,
+    /// One JSON event per line on stdout
+    Json,
+}
+
+/// Something that happened while running a command, reported on stdout.
+/// For entries written to an archive, `dest` is their name in the archive.
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    FileCopied {
+        #[serde(serialize_with = "paths::serialize")]
+        source: &'a Path,
+        #[serde(serialize_with = "paths::serialize")]
+        dest: &'a Path,
+        bytes: u64,
+        algorithm: &'a str,
+        hash: String,
+        reflinked: bool,
+        /// The source changed while it was copied, even after retrying
+        inconsistent: bool,
+    },
+    SpecialCopied {
+        kind: &'a str,
+        #[serde(serialize_with = "paths::serialize")]
+        source: &'a Path,
+        #[serde(serialize_with = "paths::serialize")]
+        dest: &'a Path,
+    },
+    HardLinked {
+        #[serde(serialize_with = "paths::serialize")]
+        source: &'a Path,
+        #[serde(serialize_with = "paths::serialize")]
+        dest: &'a Path,
+        #[serde(serialize_with = "paths::serialize")]
+        target: &'a Path,
+    },
+    MetadataRecorded {
+        kind: &'a str,
+        #[serde(serialize_with = "paths::serialize")]
+        source: &'a Path,
+        #[serde(serialize_with = "paths::option::serialize")]
+        placeholder: Option<&'a Path>,
+    },
+    FileSkipped {
+        #[serde(serialize_with = "paths::serialize")]
+        path: &'a Path,
+    },
+    ArchiveCreated {
+        #[serde(serialize_with = "paths::serialize")]
+        path: &'a Path,
+        gzip: bool,
+    },
+    Error {
+        #[serde(serialize_with = "paths::option::serialize")]
+        path: Option<&'a Path>,
+        message: String,
+    },
+    Summary {
+        #[serde(flatten)]
+        report: &'a Report,
+    },
+    VerifyProblem {
+        #[serde(serialize_with = "paths::serialize")]
+        path: &'a Path,
+        problem: &'a str,
+        detail: Option<String>,
+    },
+    VerifySummary {
+        checked: usize,
+        missing: usize,
+        extra: usize,
+        corrupted: usize,
+    },
+    DiffEntry {
+        #[serde(serialize_with = "paths::serialize")]
+        path: &'a Path,
+        change: &'a str,
+        detail: Option<String>,
+    },
+    DiffSummary {
+        added: usize,
+        modified: usize,
+        removed: usize,
+        type_changed: usize,
+        unchanged: usize,
+        unreadable: usize,
+    },
+    Config {
+        #[serde(serialize_with = "paths::serialize")]
+        path: &'a Path,
+        config: &'a Config,
+    },
+    Reset {
+        #[serde(serialize_with = "paths::serialize")]
+        path: &'a Path,
+        config: &'a Config,
+    },
+}
+
+// Output format of the command being executed. Set from the global `--format`
+// option at the start of every command.
+static FORMAT: OnceLock<Mutex<Format>> = OnceLock::new();
+
+fn format_cell() -> &'static Mutex<Format> {
+    FORMAT.get_or_init(|| Mutex::new(Format::Text))
+}
+
+pub fn set_format(format: Format) {
+    let mut guard = format_cell().lock().unwrap();
+    *guard = format;
+}
+
+pub fn format() -> Format {
+    *format_cell().lock().unwrap()
+}
+
+pub fn emit(event: Event) {
+    if format() == Format::Json {
+        match serde_json::to_string(&event) {
+            Ok(line) => println!("{}", line),
+            Err(e) => eprintln!("Failed to serialize event: {}", e),
+        }
+        return;
+    }
+
+    match event {
+        Event::FileCopied {
+            source,
+            dest,
+            algorithm,
+            hash,
+            inconsistent,
+            ..
+        } => {
+            if inconsistent {
+                warn!("{:?} kept changing while it was copied to {:?}", source, dest);
+            }
+            debug!("Copied {:?} to {:?} ({}: {})", source, dest, algorithm, hash)
+        }
+        Event::SpecialCopied { kind, source, dest } => {
+            debug!("Copied {} {:?} to {:?}", kind, source, dest)
+        }
+        Event::HardLinked { dest, target, .. } => {
+            debug!("Linked {:?} to {:?}", dest, target)
+        }
+        Event::MetadataRecorded {
+            kind,
+            source,
+            placeholder,
+        } => match placeholder {
+            Some(placeholder) => {
+                info!("Recreated {} {:?} as a placeholder at {:?}", kind, source, placeholder)
+            }
+            None => info!("Recorded {} {:?} without its contents", kind, source),
+        },
+        Event::FileSkipped { path } => trace!("Skipped {:?}", path),
+        Event::ArchiveCreated { path, gzip } => {
+            if gzip {
+                info!("Created gzipped tar archive at {:?}", path);
+            } else {
+                info!("Created tar archive at {:?}", path);
+            }
+        }
+        Event::Error { path, message } => match path {
+            Some(path) => error!("Failed to back up {}: {}", path.display(), message),
+            None => error!("Error: {}", message),
+        },
+        Event::Summary { report } => report.print(),
+        Event::VerifyProblem {
+            path,
+            problem,
+            detail,
+        } => match detail {
+            Some(detail) => warn!("{} {}: {}", problem, path.display(), detail),
+            None => warn!("{} {}", problem, path.display()),
+        },
+        Event::VerifySummary {
+            checked,
+            missing,
+            extra,
+            corrupted,
+        } => info!(
+            "Checked {} entries: {} missing, {} extra, {} corrupted",
+            checked, missing, extra, corrupted
+        ),
+        Event::DiffEntry {
+            path,
+            change,
+            detail,
+        } => match detail {
+            Some(detail) => info!("{:<12} {} ({})", change, path.display(), detail),
+            None => info!("{:<12} {}", change, path.display()),
+        },
+        Event::DiffSummary {
+            added,
+            modified,
+            removed,
+            type_changed,
+            unchanged,
+            unreadable,
+        } => info!(
+            "{} added, {} modified, {} removed, {} type changed, {} unchanged, {} unreadable",
+            added, modified, removed, type_changed, unchanged, unreadable
+        ),
+        Event::Config { path, .. } => match fs::read_to_string(path) {
+            Ok(content) => println!(
+                "Configuration file read at {}:\n{}",
+                path.display(),
+                content
+            ),
+            Err(e) => error!("Failed to read {}: {}", path.display(), e),
+        },
+        Event::Reset { path, .. } => {
+            info!("Configuration file reset at {}", path.display())
+        }
+    }
+}
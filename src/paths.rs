@@ -0,0 +1,89 @@
+use crate::*;
+use serde::{Deserializer, Serializer};
+use std::ffi::OsString;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+
+/// How a path that is not valid UTF-8 is written: its raw bytes, which are
+/// read back, and a lossy form for people and tools that only need a name.
+#[derive(Serialize)]
+struct RawPath<'a> {
+    lossy: &'a str,
+    bytes: &'a [u8],
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredPath {
+    Text(String),
+    Raw { bytes: Vec<u8> },
+}
+
+impl StoredPath {
+    fn into_path(self) -> PathBuf {
+        match self {
+            StoredPath::Text(text) => PathBuf::from(text),
+            StoredPath::Raw { bytes } => PathBuf::from(OsString::from_vec(bytes)),
+        }
+    }
+}
+
+struct SerializePath<'a>(&'a Path);
+
+impl Serialize for SerializePath<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize(&self.0, serializer)
+    }
+}
+
+/// Write `path` as a string when it is valid UTF-8, and as its raw bytes
+/// otherwise, so that no file name is lost or altered.
+pub fn serialize<P: AsRef<Path>, S: Serializer>(
+    path: &P,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let path = path.as_ref();
+    match path.to_str() {
+        Some(text) => serializer.serialize_str(text),
+        None => RawPath {
+            lossy: &path.to_string_lossy(),
+            bytes: path.as_os_str().as_bytes(),
+        }
+        .serialize(serializer),
+    }
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PathBuf, D::Error> {
+    Ok(StoredPath::deserialize(deserializer)?.into_path())
+}
+
+/// The same for optional paths.
+pub mod option {
+    use super::*;
+
+    pub fn serialize<P: AsRef<Path>, S: Serializer>(
+        path: &Option<P>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        path.as_ref()
+            .map(|path| SerializePath(path.as_ref()))
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<PathBuf>, D::Error> {
+        Ok(Option::<StoredPath>::deserialize(deserializer)?.map(StoredPath::into_path))
+    }
+}
+
+/// The same for lists of paths.
+pub mod list {
+    use super::*;
+
+    pub fn serialize<P: AsRef<Path>, S: Serializer>(
+        paths: &[P],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(paths.iter().map(|path| SerializePath(path.as_ref())))
+    }
+}
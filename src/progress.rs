@@ -0,0 +1,95 @@
+use crate::*;
+use report::format_bytes;
+use std::io::IsTerminal;
+use std::time::{Duration, Instant};
+
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Live progress line drawn on stderr while a backup runs.
+pub struct Progress {
+    enabled: bool,
+    total_files: u64,
+    total_bytes: u64,
+    files: u64,
+    bytes: u64,
+    started: Instant,
+    last_draw: Option<Instant>,
+}
+
+impl Progress {
+    /// Create a progress line for the given totals. It is only drawn when
+    /// requested, stderr is a terminal, and the output is plain text at the
+    /// default verbosity.
+    pub fn new(total_files: u64, total_bytes: u64, requested: bool) -> Self {
+        let enabled = requested
+            && io::stderr().is_terminal()
+            && output::format() == output::Format::Text
+            && log::max_level() == log::LevelFilter::Info;
+        Progress {
+            enabled,
+            total_files,
+            total_bytes,
+            files: 0,
+            bytes: 0,
+            started: Instant::now(),
+            last_draw: None,
+        }
+    }
+
+    /// Count one more file of `bytes` bytes as done.
+    pub fn advance(&mut self, bytes: u64) {
+        self.files += 1;
+        self.bytes += bytes;
+
+        if !self.enabled {
+            return;
+        }
+        let now = Instant::now();
+        if self
+            .last_draw
+            .is_some_and(|last| now.duration_since(last) < REDRAW_INTERVAL)
+            && self.files < self.total_files
+        {
+            return;
+        }
+        self.last_draw = Some(now);
+        self.draw();
+    }
+
+    fn draw(&self) {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let rate = if elapsed > 0.0 {
+            self.bytes as f64 / elapsed
+        } else {
+            0.0
+        };
+        let eta = if rate > 0.0 {
+            format!(
+                "{}s",
+                (self.total_bytes.saturating_sub(self.bytes) as f64 / rate).ceil() as u64
+            )
+        } else {
+            "-".to_string()
+        };
+        let _ = write!(
+            io::stderr(),
+            "\r\x1b[2K{}/{} files, {}/{} ({}/s), ETA {}",
+            self.files,
+            self.total_files,
+            format_bytes(self.bytes as f64),
+            format_bytes(self.total_bytes as f64),
+            format_bytes(rate),
+            eta
+        );
+        let _ = io::stderr().flush();
+    }
+
+    /// Erase the progress line.
+    pub fn finish(&mut self) {
+        if self.enabled && self.last_draw.is_some() {
+            let _ = write!(io::stderr(), "\r\x1b[2K");
+            let _ = io::stderr().flush();
+        }
+        self.enabled = false;
+    }
+}
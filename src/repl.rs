@@ -1,6 +1,8 @@
 use crate::*;
 
 pub fn run() {
+    logger::init();
+
     loop {
         print!("babycancer> ");
 
@@ -8,36 +10,69 @@ pub fn run() {
             Ok(l) => l,
             Err(e) => {
                 // For clap DisplayHelp/DisplayVersion, e's Display already prints the help/version.
-                eprintln!("Error: {}", e);
+                log::error!("Error: {}", e);
                 continue;
             }
         };
 
         if let Err(e) = execute_line(line) {
-            // Avoid prefixing with "Error:" because clap::Error display already contains
-            // the formatted error/help. Printing just the error avoids duplicated help text.
-            eprintln!("Error: {}", e);
+            report_error(&e);
         }
     }
 }
 
-pub fn execute_line(line: String) -> Result<(), Box<dyn std::error::Error>> {
-    // Parse args; handle help/version specially to avoid double-printing.
-    match command::get_args(line) {
-        Ok(args) => {
-            return command::execute_command(args)
-                .map_err(|e| Box::new(e) as Box<dyn std::error::Error>);
+/// Run a single command given on the process command line and return its exit code.
+/// Without a command, the options become session defaults of the interactive prompt.
+pub fn run_once() -> i32 {
+    logger::init();
+
+    let args = command::Args::try_parse();
+    if let Ok(args) = &args
+        && args.command.is_none()
+    {
+        if let Err(e) = logger::set_defaults(args.verbose, args.quiet, args.log_file.as_deref()) {
+            report_error(&e);
+            return e.exit_code();
+        }
+        run();
+    }
+
+    match execute_args(args) {
+        Ok(()) => error::EXIT_SUCCESS,
+        Err(e) => {
+            report_error(&e);
+            e.exit_code()
         }
+    }
+}
+
+fn report_error(e: &Error) {
+    output::emit(output::Event::Error {
+        path: None,
+        message: e.to_string(),
+    });
+}
+
+pub fn execute_line(line: String) -> Result<(), Error> {
+    execute_args(command::get_args(line))
+}
+
+fn execute_args(args: Result<command::Args, clap::Error>) -> Result<(), Error> {
+    // Parse args; handle help/version specially to avoid double-printing.
+    match args {
+        Ok(args) => command::execute_command(args),
         Err(e) => {
             use clap::error::ErrorKind;
             match e.kind() {
                 ErrorKind::DisplayHelp | ErrorKind::DisplayVersion => {
                     // Print help/version using println! and treat as success
                     println!("{}", e);
-                    return Ok(());
+                    Ok(())
                 }
                 _ => {
-                    return Err(Box::new(e));
+                    // The format option may not have been parsed; fall back to text.
+                    output::set_format(output::Format::Text);
+                    Err(Error::Usage(e))
                 }
             }
         }
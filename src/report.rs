@@ -0,0 +1,210 @@
+use crate::*;
+use log::info;
+use output::{Event, emit};
+use std::time::Instant;
+
+/// File name of the JSON report saved in the destination directory.
+pub const REPORT_FILE_NAME: &str = "backup-report.json";
+
+#[derive(Serialize)]
+pub struct Failure {
+    #[serde(serialize_with = "paths::serialize")]
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+/// Summary of a single backup run.
+#[derive(Serialize)]
+pub struct Report {
+    pub started_at: String,
+    #[serde(serialize_with = "paths::serialize")]
+    pub source_path: PathBuf,
+    #[serde(serialize_with = "paths::serialize")]
+    pub dest_path: PathBuf,
+    #[serde(serialize_with = "paths::option::serialize")]
+    pub archive: Option<PathBuf>,
+    pub files_copied: u64,
+    pub bytes_copied: u64,
+    /// Disk space used by the copied files in the source; less than
+    /// `bytes_copied` when some of them are sparse
+    pub bytes_allocated: u64,
+    pub files_reflinked: u64,
+    pub files_resumed: u64,
+    pub special_files: u64,
+    /// Sockets and other entries whose contents cannot be backed up
+    pub metadata_only: u64,
+    pub hard_links: u64,
+    pub skipped_by_filter: u64,
+    /// Files that kept changing while they were backed up; their copy may
+    /// mix old and new contents
+    #[serde(serialize_with = "paths::list::serialize")]
+    pub inconsistent: Vec<PathBuf>,
+    pub failures: Vec<Failure>,
+    pub elapsed_secs: f64,
+    pub bytes_per_sec: f64,
+    #[serde(skip)]
+    started: Instant,
+    /// Failures are only collected, for a caller that reports them itself
+    #[serde(skip)]
+    silent: bool,
+}
+
+impl Report {
+    pub fn new(source_path: &Path, dest_path: &Path) -> Self {
+        Report {
+            started_at: chrono::Local::now().to_rfc3339(),
+            source_path: source_path.to_path_buf(),
+            dest_path: dest_path.to_path_buf(),
+            archive: None,
+            files_copied: 0,
+            bytes_copied: 0,
+            bytes_allocated: 0,
+            files_reflinked: 0,
+            files_resumed: 0,
+            special_files: 0,
+            metadata_only: 0,
+            hard_links: 0,
+            skipped_by_filter: 0,
+            inconsistent: Vec::new(),
+            failures: Vec::new(),
+            elapsed_secs: 0.0,
+            bytes_per_sec: 0.0,
+            started: Instant::now(),
+            silent: false,
+        }
+    }
+
+    /// Collect failures without reporting them as backup failures, for
+    /// commands that only read the source.
+    pub fn silence_failures(&mut self) {
+        self.silent = true;
+    }
+
+    pub fn copied(&mut self, bytes: u64, allocated: u64) {
+        self.files_copied += 1;
+        self.bytes_copied += bytes;
+        self.bytes_allocated += allocated;
+    }
+
+    /// Count a copied file that shares its data with the source.
+    pub fn reflinked(&mut self) {
+        self.files_reflinked += 1;
+    }
+
+    /// Count an entry that an interrupted run already backed up.
+    pub fn resumed(&mut self) {
+        self.files_resumed += 1;
+    }
+
+    pub fn special(&mut self) {
+        self.special_files += 1;
+    }
+
+    /// Count an entry of which only the metadata was recorded.
+    pub fn recorded(&mut self) {
+        self.metadata_only += 1;
+    }
+
+    pub fn linked(&mut self) {
+        self.hard_links += 1;
+    }
+
+    pub fn skipped(&mut self) {
+        self.skipped_by_filter += 1;
+    }
+
+    /// Flag a backed-up file that changed while it was read.
+    pub fn inconsistent(&mut self, path: &Path) {
+        self.inconsistent.push(path.to_path_buf());
+    }
+
+    /// Record an entry that could not be backed up, under the path the error
+    /// is about, or `path` when it names none.
+    pub fn fail(&mut self, path: &Path, error: Error) {
+        let path = error.path().unwrap_or(path);
+        if !self.silent {
+            emit(Event::Error {
+                path: Some(path),
+                message: error.reason(),
+            });
+        }
+        self.failures.push(Failure {
+            path: path.to_path_buf(),
+            reason: error.reason(),
+        });
+    }
+
+    /// Stop the clock and compute the throughput of the run.
+    pub fn finish(&mut self) {
+        self.elapsed_secs = self.started.elapsed().as_secs_f64();
+        self.bytes_per_sec = if self.elapsed_secs > 0.0 {
+            self.bytes_copied as f64 / self.elapsed_secs
+        } else {
+            0.0
+        };
+    }
+
+    pub fn print(&self) {
+        info!("Backup report:");
+        if let Some(archive) = &self.archive {
+            info!("  Archive:            {}", archive.display());
+        }
+        info!(
+            "  Files copied:       {} ({}, {} allocated)",
+            self.files_copied,
+            format_bytes(self.bytes_copied as f64),
+            format_bytes(self.bytes_allocated as f64)
+        );
+        if self.files_reflinked > 0 {
+            info!("  Reflinked:          {}", self.files_reflinked);
+        }
+        if self.files_resumed > 0 {
+            info!("  Resumed:            {}", self.files_resumed);
+        }
+        info!("  Special files:      {}", self.special_files);
+        if self.metadata_only > 0 {
+            info!("  Metadata only:      {}", self.metadata_only);
+        }
+        info!("  Hard links:         {}", self.hard_links);
+        info!("  Skipped by filter:  {}", self.skipped_by_filter);
+        if !self.inconsistent.is_empty() {
+            info!("  Inconsistent:       {}", self.inconsistent.len());
+            for path in &self.inconsistent {
+                info!("    {}", path.display());
+            }
+        }
+        info!("  Failed:             {}", self.failures.len());
+        for failure in &self.failures {
+            info!("    {}: {}", failure.path.display(), failure.reason);
+        }
+        info!(
+            "  Elapsed:            {:.2}s ({}/s)",
+            self.elapsed_secs,
+            format_bytes(self.bytes_per_sec)
+        );
+    }
+
+    /// Save the report as JSON in the destination directory.
+    pub fn save(&self) -> Result<PathBuf, Error> {
+        let path = self.dest_path.join(REPORT_FILE_NAME);
+        let content = serde_json::to_string_pretty(self).map_err(io::Error::other).at(&path)?;
+        fs::write(&path, content).at(&path)?;
+        info!("Report saved at {}", path.display());
+        Ok(path)
+    }
+}
+
+pub fn format_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", value as u64, UNITS[unit])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
@@ -0,0 +1,158 @@
+use crate::*;
+use nix::errno::Errno;
+use nix::unistd::{Whence, lseek};
+
+/// Tar block size. Data segments are aligned to it so they can be stored as
+/// GNU sparse chunks.
+const BLOCK_SIZE: u64 = 512;
+
+/// A run of data in a sparse file: offset and length in bytes.
+pub type Segment = (u64, u64);
+
+/// Find the parts of `file` that hold data, using SEEK_DATA and SEEK_HOLE.
+///
+/// Returns `None` when the file has no holes or the file system cannot
+/// report them, in which case the file should be read as a whole.
+pub fn data_segments(
+    file: &File,
+    metadata: &fs::Metadata,
+) -> io::Result<Option<Vec<Segment>>> {
+    let len = metadata.len();
+    if metadata.blocks() * 512 >= len {
+        return Ok(None);
+    }
+
+    let mut segments: Vec<Segment> = Vec::new();
+    let mut offset = 0;
+    while offset < len {
+        let start = match lseek(file, offset as i64, Whence::SeekData) {
+            Ok(start) => start as u64,
+            // No data after `offset`: the rest of the file is a hole
+            Err(Errno::ENXIO) => break,
+            Err(Errno::EINVAL) if offset == 0 => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let end = (lseek(file, start as i64, Whence::SeekHole)? as u64).min(len);
+
+        let start = start / BLOCK_SIZE * BLOCK_SIZE;
+        let aligned_end = end.div_ceil(BLOCK_SIZE) * BLOCK_SIZE;
+        match segments.last_mut() {
+            Some((last, length)) if *last + *length >= start => {
+                *length = aligned_end.min(len) - *last;
+            }
+            _ => segments.push((start, aligned_end.min(len) - start)),
+        }
+        offset = end;
+    }
+    lseek(file, 0, Whence::SeekSet)?;
+    Ok(Some(segments))
+}
+
+/// Number of bytes stored on disk for a file.
+pub fn allocated_bytes(metadata: &fs::Metadata) -> u64 {
+    metadata.blocks() * 512
+}
+
+/// Reads the data segments of a sparse file one after another, hashing the
+/// whole file contents along the way as if the holes had been read as zeros.
+pub struct SegmentReader<'a> {
+    file: File,
+    segments: &'a [Segment],
+    len: u64,
+    position: u64,
+    remaining: u64,
+    hasher: hash::Hasher,
+}
+
+impl<'a> SegmentReader<'a> {
+    pub fn new(
+        file: File,
+        segments: &'a [Segment],
+        len: u64,
+        algorithm: hash::Algorithm,
+    ) -> Self {
+        SegmentReader {
+            file,
+            segments,
+            len,
+            position: 0,
+            remaining: 0,
+            hasher: hash::Hasher::new(algorithm),
+        }
+    }
+
+    /// Return the digest of the whole file as a lowercase hex string.
+    pub fn finalize(mut self) -> String {
+        self.hasher.update_zeros(self.len.saturating_sub(self.position));
+        self.hasher.finalize()
+    }
+}
+
+impl Read for SegmentReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.remaining == 0 {
+            let Some((&(offset, length), rest)) = self.segments.split_first() else {
+                return Ok(0);
+            };
+            self.segments = rest;
+            self.hasher.update_zeros(offset - self.position);
+            self.file.seek(io::SeekFrom::Start(offset))?;
+            self.position = offset;
+            self.remaining = length;
+        }
+
+        let max = buf.len().min(self.remaining as usize);
+        let mut bytes_read = self.file.read(&mut buf[..max])?;
+        if bytes_read == 0 {
+            // The file shrank while it was read. Zeros stand for the missing
+            // data, so that the archive still holds as much as its header says.
+            buf[..max].fill(0);
+            bytes_read = max;
+        }
+        self.hasher.update(&buf[..bytes_read]);
+        self.position += bytes_read as u64;
+        self.remaining -= bytes_read as u64;
+        Ok(bytes_read)
+    }
+}
+
+/// Fill in `header` as a GNU sparse entry for a file of `len` bytes made of
+/// `segments`. Returns the extension headers that must be written between the
+/// header and the data, for files with more segments than fit in the header.
+pub fn set_sparse_header(
+    header: &mut tar::Header,
+    segments: &[Segment],
+    len: u64,
+) -> Vec<u8> {
+    let mut chunks = segments.to_vec();
+    // The last chunk must end at the real size, even if the file ends with a hole
+    if segments.last().is_none_or(|(offset, length)| offset + length < len) {
+        chunks.push((len, 0));
+    }
+
+    header.set_entry_type(tar::EntryType::GNUSparse);
+    header.set_size(segments.iter().map(|(_, length)| length).sum());
+    let gnu = header.as_gnu_mut().expect("sparse entries need a GNU header");
+    gnu.set_real_size(len);
+
+    let (first, rest) = chunks.split_at(chunks.len().min(gnu.sparse.len()));
+    for (sparse, &(offset, length)) in gnu.sparse.iter_mut().zip(first) {
+        sparse.set_offset(offset);
+        sparse.set_length(length);
+    }
+    gnu.set_is_extended(!rest.is_empty());
+
+    let mut extensions = Vec::new();
+    let per_extension = tar::GnuExtSparseHeader::new().sparse().len();
+    let mut blocks = rest.chunks(per_extension).peekable();
+    while let Some(block) = blocks.next() {
+        let mut extension = tar::GnuExtSparseHeader::new();
+        for (sparse, &(offset, length)) in extension.sparse_mut().iter_mut().zip(block) {
+            sparse.set_offset(offset);
+            sparse.set_length(length);
+        }
+        extension.set_is_extended(blocks.peek().is_some());
+        extensions.extend_from_slice(extension.as_bytes());
+    }
+    extensions
+}
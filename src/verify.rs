@@ -0,0 +1,221 @@
+use crate::*;
+use flate2::read::GzDecoder;
+use log::{info, warn};
+use manifest::{MANIFEST_FILE_NAME, Manifest};
+use output::{Event, emit};
+use std::collections::BTreeMap;
+use walk::Kind;
+
+#[derive(Parser)]
+pub struct VerifyArgs {
+    /// Set a custom config file
+    #[arg(short, long, value_name = "FILE")]
+    config_path: Option<PathBuf>,
+}
+
+/// An entry found in the backup while scanning it.
+pub struct Found {
+    pub kind: Kind,
+    pub size: u64,
+    pub hash: Option<String>,
+}
+
+fn kind_of_tar(entry_type: tar::EntryType) -> Option<Kind> {
+    match entry_type {
+        tar::EntryType::Regular
+        | tar::EntryType::Continuous
+        | tar::EntryType::GNUSparse
+        | tar::EntryType::Link => Some(Kind::File),
+        tar::EntryType::Directory => Some(Kind::Dir),
+        tar::EntryType::Symlink => Some(Kind::Symlink),
+        tar::EntryType::Fifo => Some(Kind::Fifo),
+        tar::EntryType::Char => Some(Kind::CharDevice),
+        tar::EntryType::Block => Some(Kind::BlockDevice),
+        _ => None,
+    }
+}
+
+fn scan_archive<R: Read>(
+    archive: R,
+    algorithm: hash::Algorithm,
+) -> Result<BTreeMap<PathBuf, Found>, io::Error> {
+    let mut found: BTreeMap<PathBuf, Found> = BTreeMap::new();
+    let mut archive = tar::Archive::new(archive);
+    for entry in archive.entries()? {
+        let entry = entry?;
+        let Some(kind) = kind_of_tar(entry.header().entry_type()) else {
+            continue;
+        };
+        let path = entry.path()?.components().collect::<PathBuf>();
+
+        // Hard links have no data of their own; they hold what their target does
+        if entry.header().entry_type() == tar::EntryType::Link {
+            let target = entry.link_name()?.unwrap_or_default();
+            let (size, hash) = match found.get(target.as_ref()) {
+                Some(target) => (target.size, target.hash.clone()),
+                None => (0, None),
+            };
+            found.insert(path, Found { kind, size, hash });
+            continue;
+        }
+
+        let size = entry.size();
+        let hash = match kind {
+            Kind::File => Some(hash::hash_reader(entry, algorithm)?),
+            _ => None,
+        };
+        found.insert(path, Found { kind, size, hash });
+    }
+    Ok(found)
+}
+
+fn scan_dir(
+    root_path: &Path,
+    dir_path: &Path,
+    algorithm: hash::Algorithm,
+    found: &mut BTreeMap<PathBuf, Found>,
+) -> Result<(), Error> {
+    for entry in fs::read_dir(dir_path).at(dir_path)? {
+        let entry_path = entry.at(dir_path)?.path();
+        let relative = entry_path.strip_prefix(root_path).unwrap().to_path_buf();
+        if relative == Path::new(MANIFEST_FILE_NAME)
+            || relative == Path::new(report::REPORT_FILE_NAME)
+            || relative == Path::new(journal::JOURNAL_FILE_NAME)
+        {
+            continue;
+        }
+
+        let metadata = fs::symlink_metadata(&entry_path).at(&entry_path)?;
+        let kind = walk::kind_of(&metadata);
+        let (size, hash) = match kind {
+            Kind::Dir => {
+                scan_dir(root_path, &entry_path, algorithm, found)?;
+                (0, None)
+            }
+            Kind::File => (
+                metadata.len(),
+                Some(hash::hash_file(&entry_path, algorithm).at(&entry_path)?),
+            ),
+            _ => (0, None),
+        };
+        found.insert(relative, Found { kind, size, hash });
+    }
+    Ok(())
+}
+
+/// List and hash everything in a backup, either in the archive named
+/// `archive` or in the destination tree itself.
+pub fn scan_backup(
+    dest_path: &Path,
+    archive: Option<&Path>,
+    algorithm: hash::Algorithm,
+) -> Result<BTreeMap<PathBuf, Found>, Error> {
+    match archive {
+        Some(archive) => {
+            let archive_path = dest_path.join(archive);
+            let file = File::open(&archive_path).at(&archive_path)?;
+            let found = if archive_path.extension().is_some_and(|ext| ext == "gz") {
+                scan_archive(GzDecoder::new(file), algorithm)
+            } else {
+                scan_archive(file, algorithm)
+            };
+            found.at(&archive_path)
+        }
+        None => {
+            let mut found = BTreeMap::new();
+            scan_dir(dest_path, dest_path, algorithm, &mut found)?;
+            Ok(found)
+        }
+    }
+}
+
+/// Compare what is in the backup against its manifest and report every
+/// difference. Returns the number of missing, extra and corrupted entries.
+fn compare(manifest: &Manifest, mut found: BTreeMap<PathBuf, Found>) -> usize {
+    let (mut checked, mut missing, mut corrupted) = (0, 0, 0);
+
+    // Directories are only containers; their contents are checked one by one.
+    // Metadata-only entries have nothing in the backup to check.
+    for expected in manifest
+        .entries
+        .iter()
+        .filter(|e| e.kind != Kind::Dir && !e.kind.metadata_only() && !e.metadata_only)
+    {
+        checked += 1;
+        let Some(actual) = found.remove(&expected.path) else {
+            missing += 1;
+            emit(Event::VerifyProblem {
+                path: &expected.path,
+                problem: "missing",
+                detail: None,
+            });
+            continue;
+        };
+
+        let detail = if actual.kind != expected.kind {
+            Some(format!(
+                "expected a {}, found a {}",
+                expected.kind.as_str(),
+                actual.kind.as_str()
+            ))
+        } else if actual.size != expected.size {
+            Some(format!(
+                "expected {} bytes, found {}",
+                expected.size, actual.size
+            ))
+        } else if actual.hash != expected.hash {
+            Some(format!("{} checksum mismatch", manifest.algorithm.as_str()))
+        } else {
+            None
+        };
+        if detail.is_some() {
+            corrupted += 1;
+            emit(Event::VerifyProblem {
+                path: &expected.path,
+                problem: "corrupted",
+                detail,
+            });
+        }
+    }
+
+    let mut extra = 0;
+    for (path, _) in found
+        .iter()
+        .filter(|(_, f)| f.kind != Kind::Dir && !f.kind.metadata_only())
+    {
+        extra += 1;
+        emit(Event::VerifyProblem {
+            path,
+            problem: "extra",
+            detail: None,
+        });
+    }
+
+    emit(Event::VerifySummary {
+        checked,
+        missing,
+        extra,
+        corrupted,
+    });
+    missing + extra + corrupted
+}
+
+pub fn command_verify(args: &VerifyArgs) -> Result<(), Error> {
+    let config = config::get_config(&args.config_path)?;
+    let dest_path = Path::new(&config.path_config.dest_path);
+    let manifest = Manifest::load(dest_path)?;
+    info!(
+        "Verifying backup in {} against the manifest from {}...",
+        dest_path.display(),
+        manifest.created_at
+    );
+
+    let found = scan_backup(dest_path, manifest.archive.as_deref(), manifest.algorithm)?;
+    let problems = compare(&manifest, found);
+    if problems > 0 {
+        warn!("Backup verification found {} problem(s).", problems);
+        return Err(Error::Verification { problems });
+    }
+    info!("Backup verified successfully.");
+    Ok(())
+}
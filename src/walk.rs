@@ -0,0 +1,570 @@
+use crate::*;
+use output::{Event, emit};
+use config::{NameMatching, Symlinks};
+use ignore::IgnoreRules;
+use log::{debug, info};
+use report::Report;
+use std::collections::HashMap;
+use std::collections::hash_map;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Component;
+
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    Dir,
+    File,
+    Symlink,
+    Fifo,
+    CharDevice,
+    BlockDevice,
+    Socket,
+    /// Any other file type the platform may have
+    Unknown,
+}
+
+impl Kind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Kind::Dir => "directory",
+            Kind::File => "file",
+            Kind::Symlink => "symlink",
+            Kind::Fifo => "FIFO",
+            Kind::CharDevice => "char device",
+            Kind::BlockDevice => "block device",
+            Kind::Socket => "socket",
+            Kind::Unknown => "unknown",
+        }
+    }
+
+    /// Whether entries of this kind cannot be copied, so that only their
+    /// metadata is recorded.
+    pub fn metadata_only(&self) -> bool {
+        matches!(self, Kind::Socket | Kind::Unknown)
+    }
+}
+
+/// A file or directory selected for backup.
+pub struct Entry {
+    pub path: PathBuf,
+    pub relative: PathBuf,
+    pub kind: Kind,
+    pub metadata: fs::Metadata,
+    /// Earlier entry this file is hard-linked to, relative to the root
+    pub hard_link: Option<PathBuf>,
+    /// Where a symlink should point in the backup
+    pub link_target: Option<PathBuf>,
+}
+
+impl Entry {
+    /// Number of content bytes that will be copied for this entry.
+    pub fn size(&self) -> u64 {
+        if self.kind == Kind::File {
+            self.metadata.len()
+        } else {
+            0
+        }
+    }
+}
+
+/// Result of scanning the source tree: every entry to back up, in walk order.
+pub struct Walk {
+    pub entries: Vec<Entry>,
+    pub files: u64,
+    pub bytes: u64,
+    /// First path seen for each (device, inode) pair with several links
+    inodes: HashMap<(u64, u64), PathBuf>,
+    /// File system type of each mounted device
+    mount_types: HashMap<u64, String>,
+    /// Rules of the ignore files in the directories being walked
+    ignores: Vec<IgnoreRules>,
+    /// Whether files are matched against the file filters
+    filtered: bool,
+    /// The file name filter, compiled once for the whole walk
+    name_matcher: Option<NameMatcher>,
+}
+
+/// Virtual file systems whose contents are generated by the kernel and are
+/// never worth backing up.
+const PSEUDO_FILE_SYSTEMS: &[&str] = &[
+    "autofs",
+    "binfmt_misc",
+    "bpf",
+    "cgroup",
+    "cgroup2",
+    "configfs",
+    "debugfs",
+    "devpts",
+    "devtmpfs",
+    "efivarfs",
+    "fusectl",
+    "hugetlbfs",
+    "mqueue",
+    "nsfs",
+    "proc",
+    "pstore",
+    "rpc_pipefs",
+    "securityfs",
+    "selinuxfs",
+    "sysfs",
+    "tracefs",
+];
+
+/// Read the file system type of every mounted device from the kernel.
+/// Returns an empty map where the mount table is not available.
+fn mount_types() -> HashMap<u64, String> {
+    let content = match fs::read_to_string("/proc/self/mountinfo") {
+        Ok(content) => content,
+        Err(e) => {
+            debug!("Cannot read the mount table: {}", e);
+            return HashMap::new();
+        }
+    };
+
+    // Each line is "id parent major:minor root mount-point options
+    // [optional fields] - type source super-options"
+    let mut types = HashMap::new();
+    for line in content.lines() {
+        let mut fields = line.split(' ');
+        let device = fields.nth(2).and_then(|device| device.split_once(':'));
+        let fs_type = fields.skip_while(|field| *field != "-").nth(1);
+        if let (Some((major, minor)), Some(fs_type)) = (device, fs_type)
+            && let (Ok(major), Ok(minor)) = (major.parse(), minor.parse())
+        {
+            types.insert(nix::sys::stat::makedev(major, minor), fs_type.to_string());
+        }
+    }
+    types
+}
+
+/// Why a directory that is on a different device than its parent should not
+/// be walked, if it should not.
+fn skip_mount(
+    metadata: &fs::Metadata,
+    root_dev: u64,
+    file_config: &config::FileConfig,
+    mount_types: &HashMap<u64, String>,
+) -> Option<String> {
+    if let Some(fs_type) = mount_types.get(&metadata.dev())
+        && (PSEUDO_FILE_SYSTEMS.contains(&fs_type.as_str())
+            || (file_config.skip_tmpfs && fs_type == "tmpfs"))
+    {
+        return Some(format!("{} file system", fs_type));
+    }
+    if file_config.one_file_system && metadata.dev() != root_dev {
+        return Some("on another file system".to_string());
+    }
+    None
+}
+
+/// Header a `CACHEDIR.TAG` file must start with, per the Cache Directory
+/// Tagging Specification.
+const CACHEDIR_SIGNATURE: &[u8] = b"Signature: 8a477f597d28d172789f06886806bc55";
+
+/// The first of `markers` present in `dir_path`, if any.
+fn find_marker<'a>(dir_path: &Path, markers: &'a [String]) -> Option<&'a str> {
+    markers.iter().map(String::as_str).find(|marker| {
+        let path = dir_path.join(marker);
+        if *marker == "CACHEDIR.TAG" {
+            let mut signature = [0; CACHEDIR_SIGNATURE.len()];
+            File::open(&path)
+                .and_then(|mut file| file.read_exact(&mut signature))
+                .is_ok_and(|()| signature == CACHEDIR_SIGNATURE)
+        } else {
+            path.symlink_metadata().is_ok()
+        }
+    })
+}
+
+/// A file name regex, matched either on the raw bytes of names or on their
+/// lossy UTF-8 form.
+enum NameMatcher {
+    Bytes(regex::bytes::Regex),
+    Lossy(Regex),
+}
+
+impl NameMatcher {
+    fn new(pattern: &str, matching: NameMatching) -> Result<Self, regex::Error> {
+        Ok(match matching {
+            NameMatching::Bytes => NameMatcher::Bytes(regex::bytes::Regex::new(pattern)?),
+            NameMatching::Lossy => NameMatcher::Lossy(Regex::new(pattern)?),
+        })
+    }
+
+    /// Whether the file name of `path` matches.
+    fn is_match(&self, path: &Path) -> bool {
+        let name = path.file_name().unwrap_or(path.as_os_str());
+        match self {
+            NameMatcher::Bytes(re) => re.is_match(name.as_bytes()),
+            NameMatcher::Lossy(re) => re.is_match(&name.to_string_lossy()),
+        }
+    }
+}
+
+/// Validate the file filters, and compile the file name filter if there is
+/// one.
+fn check_filters(file_config: &config::FileConfig) -> Result<Option<NameMatcher>, Error> {
+    let mut name_matcher = None;
+    if let Some(ref name) = file_config.file_name {
+        let matcher = NameMatcher::new(name, file_config.name_matching)
+            .map_err(|e| Error::Filter(format!("invalid regex pattern '{}': {}", name, e)))?;
+        name_matcher = Some(matcher);
+    }
+
+    if let Some(ref date) = file_config.date
+        && !date.is_empty()
+    {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .map_err(|e| Error::Filter(format!("invalid date '{}': {}", date, e)))?;
+    }
+
+    Ok(name_matcher)
+}
+
+fn check_file_properties(
+    root_path: &Path,
+    file_path: &Path,
+    metadata: &fs::Metadata,
+    file_config: &config::FileConfig,
+    name_matcher: Option<&NameMatcher>,
+) -> Result<bool, Error> {
+    if let Some(ref config_path) = file_config.file_path
+        && !(file_path).starts_with(root_path.join(config_path))
+    {
+        return Ok(false);
+    }
+
+    if let Some(matcher) = name_matcher
+        && !matcher.is_match(file_path)
+    {
+        return Ok(false);
+    }
+
+    if let Some(ref date) = file_config.date
+        && !date.is_empty()
+    {
+        let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .map_err(|e| Error::Filter(e.to_string()))?;
+        let file_date = metadata.modified().at(file_path)?;
+        let file_date = chrono::DateTime::<chrono::Local>::from(file_date)
+            .naive_local()
+            .date();
+        if file_date != date {
+            return Ok(false);
+        }
+    }
+
+    if let Some(size) = file_config.size
+        && size != 0
+    {
+        let file_size = metadata.len() as i64;
+        if file_size < size {
+            return Ok(false);
+        }
+    }
+
+    if let Some(ref user_name) = file_config.user
+        && !user_name.is_empty()
+    {
+        let owner_uid = metadata.uid();
+        let owner_name =
+            get_user_by_uid(owner_uid).map(|u| u.name().to_string_lossy().into_owned());
+        if owner_name != Some(user_name.clone()) {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+pub fn kind_of(metadata: &fs::Metadata) -> Kind {
+    let file_type = metadata.file_type();
+    if file_type.is_dir() {
+        Kind::Dir
+    } else if file_type.is_symlink() {
+        Kind::Symlink
+    } else if file_type.is_fifo() {
+        Kind::Fifo
+    } else if file_type.is_char_device() {
+        Kind::CharDevice
+    } else if file_type.is_block_device() {
+        Kind::BlockDevice
+    } else if file_type.is_socket() {
+        Kind::Socket
+    } else if file_type.is_file() {
+        Kind::File
+    } else {
+        Kind::Unknown
+    }
+}
+
+/// Remove `.` and `..` components without touching the file system.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Make a symlink that points inside `root_path` point to the same file
+/// relative to itself, so that it stays inside the backup. Links pointing
+/// outside the source are kept as they are.
+fn rewrite_link(root_path: &Path, link_path: &Path, target: PathBuf) -> PathBuf {
+    let (Ok(root), Ok(link_dir)) = (
+        std::path::absolute(root_path),
+        std::path::absolute(link_path.parent().unwrap()),
+    ) else {
+        return target;
+    };
+    let (root, link_dir) = (normalize(&root), normalize(&link_dir));
+    let resolved = normalize(&link_dir.join(&target));
+    if !resolved.starts_with(&root) {
+        return target;
+    }
+
+    let common = link_dir
+        .components()
+        .zip(resolved.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut rewritten = PathBuf::new();
+    for _ in link_dir.components().skip(common) {
+        rewritten.push("..");
+    }
+    rewritten.extend(resolved.components().skip(common));
+    if rewritten.as_os_str().is_empty() {
+        rewritten.push(".");
+    }
+    rewritten
+}
+
+fn walk_dir(
+    root_path: &Path,
+    dir_path: &Path,
+    file_config: &config::FileConfig,
+    ancestors: &mut Vec<(u64, u64)>,
+    walk: &mut Walk,
+    report: &mut Report,
+) -> Result<(), Error> {
+    // Entries of `dir_path` are as deep as the number of directories above them
+    if file_config.max_depth.is_some_and(|max_depth| ancestors.len() > max_depth) {
+        return Ok(());
+    }
+
+    // A directory that cannot be listed is left out like an unreadable file.
+    // Only the root failing means there is nothing to back up.
+    let read_dir = match fs::read_dir(dir_path).at(dir_path) {
+        Ok(read_dir) => read_dir,
+        Err(e) if dir_path != root_path => {
+            report.fail(dir_path, e);
+            return Ok(());
+        }
+        Err(e) => return Err(e),
+    };
+
+    let mut has_ignores = false;
+    if file_config.ignore_files {
+        match IgnoreRules::read(dir_path) {
+            Ok(Some(rules)) => {
+                walk.ignores.push(rules);
+                has_ignores = true;
+            }
+            Ok(None) => {}
+            Err(e) => report.fail(dir_path, e),
+        }
+    }
+
+    for entry in read_dir {
+        let entry = match entry.at(dir_path) {
+            Ok(entry) => entry,
+            Err(e) => {
+                report.fail(dir_path, e);
+                continue;
+            }
+        };
+        let entry_path = entry.path();
+        let relative = entry_path.strip_prefix(root_path).unwrap().to_path_buf();
+
+        let mut metadata = match fs::symlink_metadata(&entry_path).at(&entry_path) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                report.fail(&entry_path, e);
+                continue;
+            }
+        };
+        let mut link_target = None;
+        if metadata.file_type().is_symlink() {
+            match file_config.symlinks {
+                Symlinks::Preserve | Symlinks::Rewrite => {
+                    let target = match fs::read_link(&entry_path).at(&entry_path) {
+                        Ok(target) => target,
+                        Err(e) => {
+                            report.fail(&entry_path, e);
+                            continue;
+                        }
+                    };
+                    link_target = Some(match file_config.symlinks {
+                        Symlinks::Rewrite => rewrite_link(root_path, &entry_path, target),
+                        _ => target,
+                    });
+                }
+                Symlinks::Skip => {
+                    report.skipped();
+                    emit(Event::FileSkipped { path: &entry_path });
+                    continue;
+                }
+                Symlinks::Follow => match fs::metadata(&entry_path).at(&entry_path) {
+                    Ok(target) => metadata = target,
+                    Err(e) => {
+                        report.fail(&entry_path, e);
+                        continue;
+                    }
+                },
+            }
+        }
+
+        if ignore::is_ignored(&walk.ignores, &entry_path, metadata.is_dir()) {
+            report.skipped();
+            emit(Event::FileSkipped { path: &entry_path });
+            continue;
+        }
+
+        if metadata.is_dir() {
+            let (parent_dev, _) = *ancestors.last().unwrap();
+            if metadata.dev() != parent_dev {
+                let (root_dev, _) = ancestors[0];
+                if let Some(reason) =
+                    skip_mount(&metadata, root_dev, file_config, &walk.mount_types)
+                {
+                    info!("Not backing up {}: {}", entry_path.display(), reason);
+                    report.skipped();
+                    emit(Event::FileSkipped { path: &entry_path });
+                    continue;
+                }
+            }
+
+            if let Some(marker) = find_marker(&entry_path, &file_config.exclude_markers) {
+                info!("Not backing up {}: contains {}", entry_path.display(), marker);
+                report.skipped();
+                emit(Event::FileSkipped { path: &entry_path });
+                continue;
+            }
+
+            // Following a link to a directory that is being walked would
+            // never end.
+            let inode = (metadata.dev(), metadata.ino());
+            if ancestors.contains(&inode) {
+                let error = Error::Io {
+                    path: entry_path.clone(),
+                    source: nix::errno::Errno::ELOOP.into(),
+                };
+                report.fail(&entry_path, error);
+                continue;
+            }
+            walk.entries.push(Entry {
+                path: entry_path.clone(),
+                relative,
+                kind: Kind::Dir,
+                metadata,
+                hard_link: None,
+                link_target: None,
+            });
+            ancestors.push(inode);
+            walk_dir(root_path, &entry_path, file_config, ancestors, walk, report)?;
+            ancestors.pop();
+            continue;
+        }
+
+        let selected = if walk.filtered {
+            let name_matcher = walk.name_matcher.as_ref();
+            check_file_properties(root_path, &entry_path, &metadata, file_config, name_matcher)
+        } else {
+            Ok(true)
+        };
+        match selected {
+            Ok(true) => {}
+            Ok(false) => {
+                report.skipped();
+                emit(Event::FileSkipped { path: &entry_path });
+                continue;
+            }
+            Err(e) => {
+                report.fail(&entry_path, e);
+                continue;
+            }
+        }
+
+        let mut entry = Entry {
+            path: entry_path,
+            relative,
+            kind: kind_of(&metadata),
+            metadata,
+            hard_link: None,
+            link_target,
+        };
+        if entry.kind == Kind::File && entry.metadata.nlink() > 1 {
+            let inode = (entry.metadata.dev(), entry.metadata.ino());
+            match walk.inodes.entry(inode) {
+                hash_map::Entry::Occupied(first) => entry.hard_link = Some(first.get().clone()),
+                hash_map::Entry::Vacant(slot) => {
+                    slot.insert(entry.relative.clone());
+                }
+            }
+        }
+        walk.files += 1;
+        walk.bytes += entry.size();
+        walk.entries.push(entry);
+    }
+
+    if has_ignores {
+        walk.ignores.pop();
+    }
+    Ok(())
+}
+
+/// Scan `root_path` and select the entries matching `file_config`. Unless
+/// `filtered` is set, the file filters (path, name, date, size and user) are
+/// left out and every file the walk reaches is selected.
+///
+/// Filtered and unreadable files are recorded in `report`.
+pub fn walk(
+    root_path: &Path,
+    file_config: &config::FileConfig,
+    filtered: bool,
+    report: &mut Report,
+) -> Result<Walk, Error> {
+    let name_matcher = if filtered {
+        check_filters(file_config)?
+    } else {
+        None
+    };
+
+    let mut walk = Walk {
+        entries: Vec::new(),
+        files: 0,
+        bytes: 0,
+        inodes: HashMap::new(),
+        mount_types: mount_types(),
+        ignores: Vec::new(),
+        filtered,
+        name_matcher,
+    };
+    let root = fs::metadata(root_path).at(root_path)?;
+    let mut ancestors = vec![(root.dev(), root.ino())];
+    walk_dir(
+        root_path,
+        root_path,
+        file_config,
+        &mut ancestors,
+        &mut walk,
+        report,
+    )?;
+    Ok(walk)
+}
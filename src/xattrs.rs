@@ -0,0 +1,73 @@
+use crate::*;
+use log::warn;
+use nix::errno::Errno;
+
+/// Extended attributes kept in backups: file capabilities, and the access and
+/// default POSIX ACLs.
+pub const PRESERVED: &[&str] = &[
+    "security.capability",
+    "system.posix_acl_access",
+    "system.posix_acl_default",
+];
+
+/// Prefix of the PAX records holding extended attributes, as written by GNU
+/// and star tar.
+const PAX_PREFIX: &str = "SCHILY.xattr.";
+
+/// Name and value of each preserved attribute.
+pub type Attributes = Vec<(&'static str, Vec<u8>)>;
+
+fn unsupported(e: &io::Error) -> bool {
+    e.raw_os_error() == Some(Errno::ENOTSUP as i32)
+}
+
+/// Read the preserved attributes of `path`, following symlinks. File systems
+/// without extended attributes have none.
+pub fn read(path: &Path) -> Result<Attributes, io::Error> {
+    let mut attributes = Vec::new();
+    for &name in PRESERVED {
+        match xattr::get_deref(path, name) {
+            Ok(Some(value)) => attributes.push((name, value)),
+            Ok(None) => {}
+            Err(e) if unsupported(&e) => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(attributes)
+}
+
+/// Set `attributes` on `path`. This must come after anything that changes
+/// the mode, owner or contents, which would reset them.
+///
+/// Setting capabilities needs privileges, and the destination may not
+/// support attributes at all; the attribute is then left out with a warning.
+pub fn apply(path: &Path, attributes: &Attributes) -> Result<(), io::Error> {
+    for (name, value) in attributes {
+        match xattr::set(path, name, value) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied || unsupported(&e) => {
+                warn!("Cannot set {} on {}: {}", name, path.display(), e);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// Encode `attributes` as the records of a PAX extended header.
+pub fn pax_records(attributes: &Attributes) -> Vec<u8> {
+    let mut records = Vec::new();
+    for (name, value) in attributes {
+        // Each record is "<length> <key>=<value>\n", where the length counts
+        // the whole record, its own digits included.
+        let body = PAX_PREFIX.len() + name.len() + value.len() + 3;
+        let mut length = body + body.to_string().len();
+        if length.to_string().len() + body != length {
+            length += 1;
+        }
+        records.extend_from_slice(format!("{} {}{}=", length, PAX_PREFIX, name).as_bytes());
+        records.extend_from_slice(value);
+        records.push(b'\n');
+    }
+    records
+}
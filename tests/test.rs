@@ -417,7 +417,7 @@ fn test_gzip_with_tar() {
         let mut gz = flate2::read::GzDecoder::new(file);
         let mut contents = Vec::new();
         gz.read_to_end(&mut contents).expect("Failed to decompress gzip file");
-        assert!(contents.len() > 0, "Gzipped tar archive should not be empty");
+        assert!(!contents.is_empty(), "Gzipped tar archive should not be empty");
     });
 }
 
@@ -453,3 +453,2061 @@ fn test_gzip_reset_all() {
         assert!(repl::execute_line("config --output".to_string()).is_ok());
     });
 }
+
+// Error type and exit code tests
+#[test]
+fn test_error_kinds() {
+    with_clean_config("test_error_kinds", |config_path| {
+        assert!(matches!(
+            repl::execute_line("backup -c /tmp/nonexistent.toml".to_string()),
+            Err(Error::Io { .. })
+        ));
+        assert!(matches!(
+            repl::execute_line(format!("backup -c {} --interval 0", config_path)),
+            Err(Error::Usage(_))
+        ));
+        assert!(matches!(
+            repl::execute_line(format!("config -c {} --file-name [unclosed", config_path)),
+            Err(Error::Filter(_))
+        ));
+
+        // A date that cannot be parsed is reported as a filter error, not a panic
+        assert!(repl::execute_line(format!("config -c {} --date yesterday", config_path)).is_ok());
+        assert!(matches!(
+            repl::execute_line(format!("backup -c {}", config_path)),
+            Err(Error::Filter(_))
+        ));
+    });
+}
+
+#[test]
+fn test_exit_codes() {
+    let partial = Error::Partial { failed: 1 };
+    assert_eq!(partial.exit_code(), error::EXIT_PARTIAL);
+
+    let usage = repl::execute_line("invalid_command".to_string()).unwrap_err();
+    assert_eq!(usage.exit_code(), error::EXIT_USAGE);
+
+    let fatal = repl::execute_line("backup -c /tmp/nonexistent.toml".to_string()).unwrap_err();
+    assert_eq!(fatal.exit_code(), error::EXIT_FAILURE);
+
+    assert_ne!(error::EXIT_SUCCESS, error::EXIT_PARTIAL);
+    assert_ne!(error::EXIT_FAILURE, error::EXIT_PARTIAL);
+}
+
+#[test]
+fn test_partial_backup() {
+    with_clean_config("test_partial", |config_path| {
+        // A broken link cannot be followed, but the rest of the tree is still copied
+        let src = PathBuf::from("tests/example/partial_src");
+        let dest = PathBuf::from("tests/example/partial_dest");
+        let _ = fs::remove_dir_all(&src);
+        let _ = fs::remove_dir_all(&dest);
+        fs::create_dir_all(&src).unwrap();
+        fs::write(src.join("kept.txt"), "kept").unwrap();
+        std::os::unix::fs::symlink("missing.txt", src.join("broken_link")).unwrap();
+
+        assert!(repl::execute_line(format!(
+            "config -c {} --source-path {} --dest-path {} --symlinks follow",
+            config_path,
+            src.display(),
+            dest.display()
+        )).is_ok());
+        let result = repl::execute_line(format!("backup -c {}", config_path));
+        assert!(matches!(result, Err(Error::Partial { failed: 1 })));
+        assert!(dest.join("kept.txt").exists());
+
+        let _ = fs::remove_dir_all(&src);
+        let _ = fs::remove_dir_all(&dest);
+    });
+}
+
+#[test]
+fn test_backup_report() {
+    with_clean_config("test_report", |config_path| {
+        let dest = PathBuf::from("tests/example/report_dest");
+        let _ = fs::remove_dir_all(&dest);
+
+        assert!(repl::execute_line(format!(
+            "config -c {} --dest-path {} --report true",
+            config_path,
+            dest.display()
+        )).is_ok());
+        assert!(repl::execute_line(format!("backup -c {}", config_path)).is_ok());
+
+        let content = fs::read_to_string(dest.join(report::REPORT_FILE_NAME)).expect("Report should be saved");
+        let report: serde_json::Value = serde_json::from_str(&content).expect("Report should be valid JSON");
+        assert_eq!(report["files_copied"], 3);
+        assert_eq!(report["special_files"], 1);
+        assert_eq!(report["skipped_by_filter"], 0);
+        assert!(report["bytes_copied"].as_u64().unwrap() > 0);
+        assert!(report["failures"].as_array().unwrap().is_empty());
+
+        // Filtered files are counted as skipped
+        assert!(repl::execute_line(format!("config -c {} --file-name txt$", config_path)).is_ok());
+        assert!(repl::execute_line(format!("backup -c {}", config_path)).is_ok());
+        let content = fs::read_to_string(dest.join(report::REPORT_FILE_NAME)).unwrap();
+        let report: serde_json::Value = serde_json::from_str(&content).unwrap();
+        assert_eq!(report["files_copied"], 2);
+        assert_eq!(report["skipped_by_filter"], 2);
+
+        let _ = fs::remove_dir_all(&dest);
+    });
+}
+
+// JSON output tests
+fn run_json(args: &[&str]) -> Vec<serde_json::Value> {
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_babycancer"))
+        .args(args)
+        .args(["--format", "json"])
+        .output()
+        .expect("Failed to run babycancer");
+    String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("Every stdout line should be JSON"))
+        .collect()
+}
+
+#[test]
+fn test_json_output() {
+    with_clean_config("test_json", |config_path| {
+        let dest = "tests/example/json_dest";
+        let _ = fs::remove_dir_all(dest);
+        assert!(repl::execute_line(format!("config -c {} --dest-path {}", config_path, dest)).is_ok());
+
+        let events = run_json(&["backup", "-c", &config_path]);
+        let copied = events.iter().filter(|e| e["event"] == "file_copied").count();
+        assert_eq!(copied, 3);
+        let summary = events.last().unwrap();
+        assert_eq!(summary["event"], "summary");
+        assert_eq!(summary["files_copied"], 3);
+
+        let events = run_json(&["config", "-c", &config_path, "--output"]);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0]["event"], "config");
+        assert_eq!(events[0]["config"]["path_config"]["dest_path"], dest);
+
+        let events = run_json(&["reset", "-c", &config_path, "--user"]);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0]["event"], "reset");
+
+        let events = run_json(&["backup", "-c", "/tmp/nonexistent.toml"]);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0]["event"], "error");
+
+        let _ = fs::remove_dir_all(dest);
+    });
+}
+
+// Logging tests
+fn run_text(args: &[&str]) -> String {
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_babycancer"))
+        .args(args)
+        .output()
+        .expect("Failed to run babycancer");
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_log_levels() {
+    with_clean_config("test_log_levels", |config_path| {
+        let dest = "tests/example/log_dest";
+        let log_file = "tests/example/test_log_levels.log";
+        let _ = fs::remove_file(log_file);
+        assert!(repl::execute_line(format!("config -c {} --dest-path {}", config_path, dest)).is_ok());
+
+        // Per-file messages are only shown with -v
+        let stdout = run_text(&["backup", "-c", &config_path]);
+        assert!(stdout.contains("Backup report"));
+        assert!(!stdout.contains("Copied"));
+
+        let stdout = run_text(&["backup", "-c", &config_path, "-v"]);
+        assert!(stdout.contains("Copied"));
+
+        let stdout = run_text(&["backup", "-c", &config_path, "-q"]);
+        assert!(stdout.is_empty());
+
+        // The log file gets the same messages with timestamps and levels
+        run_text(&["backup", "-c", &config_path, "-v", "--log-file", log_file]);
+        let log = fs::read_to_string(log_file).expect("Log file should be written");
+        assert!(log.contains("DEBUG Copied"));
+        assert!(log.contains("INFO  Backup completed successfully."));
+
+        let _ = fs::remove_file(log_file);
+        let _ = fs::remove_dir_all(dest);
+    });
+}
+
+#[test]
+fn test_tar_ignores_filters() {
+    with_clean_config("test_tar_filters", |config_path| {
+        let dest = PathBuf::from("tests/example/tar_filter_dest");
+        let _ = fs::remove_dir_all(&dest);
+        assert!(repl::execute_line(format!(
+            "config -c {} --dest-path {} --tar true --file-name txt$ --report true",
+            config_path,
+            dest.display()
+        )).is_ok());
+        assert!(repl::execute_line(format!("backup -c {} --no-progress", config_path)).is_ok());
+
+        let mut archive = tar::Archive::new(fs::File::open(dest.join("backup.tar")).unwrap());
+        let mut names: Vec<String> = archive
+            .entries()
+            .unwrap()
+            .map(|e| e.unwrap().path().unwrap().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            ["my_folder", "my_folder/my_text.txt", "my_pic.png", "my_symlink", "my_test.txt"]
+        );
+
+        let content = fs::read_to_string(dest.join(report::REPORT_FILE_NAME)).unwrap();
+        let report: serde_json::Value = serde_json::from_str(&content).unwrap();
+        assert_eq!(report["files_copied"], 3);
+        assert_eq!(report["special_files"], 1);
+        assert_eq!(report["skipped_by_filter"], 0);
+
+        // Nothing looks removed when the filters would have left files out
+        let events = run_json(&["diff", "-c", &config_path]);
+        let summary = events.iter().find(|e| e["event"] == "diff_summary").unwrap();
+        assert_eq!(summary["added"], 0);
+        assert_eq!(summary["removed"], 0);
+
+        let _ = fs::remove_dir_all(&dest);
+    });
+}
+
+#[test]
+fn test_parallel_copy() {
+    with_clean_config("test_parallel", |config_path| {
+        let src = PathBuf::from("tests/example/parallel_src");
+        let dest = PathBuf::from("tests/example/parallel_dest");
+        let _ = fs::remove_dir_all(&src);
+        let _ = fs::remove_dir_all(&dest);
+        for i in 0..20 {
+            let dir = src.join(format!("dir{}", i % 3));
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join(format!("file{}.txt", i)), "x".repeat(i * 100)).unwrap();
+        }
+        assert!(repl::execute_line(format!(
+            "config -c {} --source-path {} --dest-path {}",
+            config_path,
+            src.display(),
+            dest.display()
+        )).is_ok());
+
+        let copied = |events: Vec<serde_json::Value>| -> Vec<String> {
+            events
+                .iter()
+                .filter(|e| e["event"] == "file_copied")
+                .map(|e| e["source"].as_str().unwrap().to_string())
+                .collect()
+        };
+        let serial = copied(run_json(&["backup", "-c", &config_path]));
+        assert_eq!(serial.len(), 20);
+
+        assert!(repl::execute_line(format!("config -c {} --jobs 4", config_path)).is_ok());
+        let _ = fs::remove_dir_all(&dest);
+        let parallel = copied(run_json(&["backup", "-c", &config_path]));
+        assert_eq!(parallel, serial, "Events should be reported in walk order");
+        for i in 0..20 {
+            let file = dest.join(format!("dir{}/file{}.txt", i % 3, i));
+            assert_eq!(fs::read(file).unwrap().len(), i * 100);
+        }
+
+        assert!(repl::execute_line(format!("reset -c {} --jobs", config_path)).is_ok());
+        let _ = fs::remove_dir_all(&src);
+        let _ = fs::remove_dir_all(&dest);
+    });
+}
+
+#[test]
+fn test_verify_modes() {
+    with_clean_config("test_verify", |config_path| {
+        let dest = PathBuf::from("tests/example/verify_dest");
+        for mode in ["none", "size", "reread"] {
+            let _ = fs::remove_dir_all(&dest);
+            assert!(repl::execute_line(format!(
+                "config -c {} --dest-path {} --verify {}",
+                config_path,
+                dest.display(),
+                mode
+            )).is_ok());
+
+            let events = run_json(&["backup", "-c", &config_path]);
+            for event in events.iter().filter(|e| e["event"] == "file_copied") {
+                let source = fs::read(event["source"].as_str().unwrap()).unwrap();
+                let copy = fs::read(event["dest"].as_str().unwrap()).unwrap();
+                assert_eq!(source, copy);
+                assert_eq!(event["bytes"], source.len());
+                assert_eq!(event["algorithm"], "crc32");
+                assert_eq!(event["hash"], format!("{:08x}", crc32fast::hash(&source)));
+            }
+            assert_eq!(events.last().unwrap()["files_copied"], 3);
+        }
+
+        assert!(repl::execute_line(format!("config -c {} --verify everything", config_path)).is_err());
+        let _ = fs::remove_dir_all(&dest);
+    });
+}
+
+#[test]
+fn test_hash_algorithms() {
+    use sha2::Digest;
+
+    with_clean_config("test_hash", |config_path| {
+        let dest = PathBuf::from("tests/example/hash_dest");
+        let source = fs::read("tests/example/src/my_test.txt").unwrap();
+        let expected = [
+            ("crc32", format!("{:08x}", crc32fast::hash(&source))),
+            ("sha256", format!("{:x}", sha2::Sha256::digest(&source))),
+            ("blake3", blake3::hash(&source).to_hex().to_string()),
+            ("xxh3", format!("{:016x}", xxhash_rust::xxh3::xxh3_64(&source))),
+        ];
+
+        for (algorithm, digest) in expected {
+            let _ = fs::remove_dir_all(&dest);
+            assert!(repl::execute_line(format!(
+                "config -c {} --dest-path {} --hash {}",
+                config_path,
+                dest.display(),
+                algorithm
+            )).is_ok());
+
+            let events = run_json(&["backup", "-c", &config_path]);
+            let event = events
+                .iter()
+                .find(|e| e["event"] == "file_copied" && e["source"] == "tests/example/src/my_test.txt")
+                .expect("my_test.txt should be copied");
+            assert_eq!(event["algorithm"], algorithm);
+            assert_eq!(event["hash"], digest);
+        }
+
+        let _ = fs::remove_dir_all(&dest);
+    });
+}
+
+#[test]
+fn test_verify_backup() {
+    with_clean_config("test_verify_backup", |config_path| {
+        let dest = PathBuf::from("tests/example/verify_backup_dest");
+        let _ = fs::remove_dir_all(&dest);
+        assert!(
+            repl::execute_line(format!(
+                "config -c {} --dest-path {}",
+                config_path,
+                dest.display()
+            ))
+            .is_ok()
+        );
+        assert!(repl::execute_line(format!("backup -c {}", config_path)).is_ok());
+        assert!(dest.join(manifest::MANIFEST_FILE_NAME).exists());
+        // The manifest is renamed into place, leaving no temporary file
+        assert!(fs::read_dir(&dest).unwrap().all(|e| {
+            !e.unwrap().file_name().to_string_lossy().starts_with(".babycancer-tmp-")
+        }));
+        assert!(repl::execute_line(format!("verify -c {}", config_path)).is_ok());
+
+        // Corrupt one file, delete another and add one that was never backed up
+        fs::write(dest.join("my_test.txt"), "bit rot").unwrap();
+        fs::remove_file(dest.join("my_pic.png")).unwrap();
+        fs::write(dest.join("extra.txt"), "extra").unwrap();
+
+        let result = repl::execute_line(format!("verify -c {}", config_path));
+        assert!(matches!(result, Err(Error::Verification { problems: 3 })));
+
+        let events = run_json(&["verify", "-c", &config_path]);
+        let problem = |path: &str| {
+            events
+                .iter()
+                .find(|e| e["event"] == "verify_problem" && e["path"] == path)
+                .map(|e| e["problem"].as_str().unwrap().to_string())
+        };
+        assert_eq!(problem("my_test.txt").as_deref(), Some("corrupted"));
+        assert_eq!(problem("my_pic.png").as_deref(), Some("missing"));
+        assert_eq!(problem("extra.txt").as_deref(), Some("extra"));
+        assert_eq!(problem("my_symlink"), None);
+
+        let summary = events
+            .iter()
+            .find(|e| e["event"] == "verify_summary")
+            .expect("verify should print a summary");
+        assert_eq!(summary["checked"], 4);
+        assert_eq!(summary["extra"], 1);
+        let counted = ["missing", "extra", "corrupted"].map(|k| summary[k].as_u64().unwrap());
+        assert_eq!(counted.iter().sum::<u64>(), 3);
+
+        let _ = fs::remove_dir_all(&dest);
+    });
+}
+
+#[test]
+fn test_verify_archive() {
+    with_clean_config("test_verify_archive", |config_path| {
+        let dest = PathBuf::from("tests/example/verify_archive_dest");
+        for gzip in ["false", "true"] {
+            let _ = fs::remove_dir_all(&dest);
+            assert!(
+                repl::execute_line(format!(
+                    "config -c {} --dest-path {} --tar true --gzip {}",
+                    config_path,
+                    dest.display(),
+                    gzip
+                ))
+                .is_ok()
+            );
+            assert!(repl::execute_line(format!("backup -c {}", config_path)).is_ok());
+            assert!(repl::execute_line(format!("verify -c {}", config_path)).is_ok());
+        }
+
+        // Replace the archive with one missing a file
+        let _ = fs::remove_dir_all(&dest);
+        assert!(
+            repl::execute_line(format!(
+                "config -c {} --dest-path {} --gzip false",
+                config_path,
+                dest.display()
+            ))
+            .is_ok()
+        );
+        assert!(repl::execute_line(format!("backup -c {}", config_path)).is_ok());
+        let archive = dest.join("backup.tar");
+        let mut builder = tar::Builder::new(Vec::new());
+        builder
+            .append_path_with_name("tests/example/src/my_test.txt", "my_test.txt")
+            .unwrap();
+        fs::write(&archive, builder.into_inner().unwrap()).unwrap();
+
+        let result = repl::execute_line(format!("verify -c {}", config_path));
+        assert!(matches!(result, Err(Error::Verification { .. })));
+
+        let _ = fs::remove_dir_all(&dest);
+    });
+}
+
+#[test]
+fn test_diff() {
+    with_clean_config("test_diff", |config_path| {
+        let source = PathBuf::from("tests/example/diff_src");
+        let dest = PathBuf::from("tests/example/diff_dest");
+        let _ = fs::remove_dir_all(&source);
+        let _ = fs::remove_dir_all(&dest);
+        fs::create_dir_all(source.join("sub")).unwrap();
+        fs::write(source.join("same.txt"), "same").unwrap();
+        fs::write(source.join("grow.txt"), "short").unwrap();
+        fs::write(source.join("sub/gone.txt"), "gone").unwrap();
+        fs::write(source.join("link"), "file").unwrap();
+        assert!(
+            repl::execute_line(format!(
+                "config -c {} --source-path {} --dest-path {}",
+                config_path,
+                source.display(),
+                dest.display()
+            ))
+            .is_ok()
+        );
+
+        let changes = |extra: &[&str]| {
+            let mut args = vec!["diff", "-c", &config_path];
+            args.extend_from_slice(extra);
+            run_json(&args)
+                .into_iter()
+                .filter(|e| e["event"] == "diff_entry")
+                .map(|e| {
+                    (
+                        e["path"].as_str().unwrap().to_string(),
+                        e["change"].as_str().unwrap().to_string(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // Nothing is backed up yet
+        assert_eq!(changes(&[]).len(), 4);
+        assert!(changes(&[]).iter().all(|(_, change)| change == "added"));
+
+        assert!(repl::execute_line(format!("backup -c {}", config_path)).is_ok());
+        assert!(changes(&[]).is_empty());
+        assert!(changes(&["--checksum"]).is_empty());
+
+        fs::write(source.join("grow.txt"), "much longer").unwrap();
+        fs::write(source.join("new.txt"), "new").unwrap();
+        fs::remove_file(source.join("sub/gone.txt")).unwrap();
+        fs::remove_file(source.join("link")).unwrap();
+        std::os::unix::fs::symlink("same.txt", source.join("link")).unwrap();
+
+        let expected = vec![
+            ("grow.txt".to_string(), "modified".to_string()),
+            ("link".to_string(), "type_changed".to_string()),
+            ("new.txt".to_string(), "added".to_string()),
+            ("sub/gone.txt".to_string(), "removed".to_string()),
+        ];
+        let mut actual = changes(&[]);
+        actual.sort();
+        assert_eq!(actual, expected);
+
+        // Without a manifest the destination tree is scanned instead
+        fs::remove_file(dest.join(manifest::MANIFEST_FILE_NAME)).unwrap();
+        let mut actual = changes(&[]);
+        actual.sort();
+        assert_eq!(actual, expected);
+
+        let _ = fs::remove_dir_all(&source);
+        let _ = fs::remove_dir_all(&dest);
+    });
+}
+
+#[test]
+fn test_atomic_writes() {
+    with_clean_config("test_atomic_writes", |config_path| {
+        let dest = PathBuf::from("tests/example/atomic_dest");
+        let _ = fs::remove_dir_all(&dest);
+        fs::create_dir_all(dest.join("my_folder")).unwrap();
+
+        // Leftovers of an interrupted run
+        let stale = [
+            dest.join(".babycancer-tmp-4242-0"),
+            dest.join("my_folder/.babycancer-tmp-4242-1"),
+            dest.join(".babycancer-tmp-4242-2"),
+        ];
+        for path in &stale {
+            fs::write(path, "partial").unwrap();
+        }
+
+        assert!(
+            repl::execute_line(format!(
+                "config -c {} --dest-path {}",
+                config_path,
+                dest.display()
+            ))
+            .is_ok()
+        );
+        assert!(repl::execute_line(format!("backup -c {}", config_path)).is_ok());
+        for path in &stale {
+            assert!(!path.exists(), "{} should be removed", path.display());
+        }
+        assert_eq!(
+            fs::read(dest.join("my_test.txt")).unwrap(),
+            fs::read("tests/example/src/my_test.txt").unwrap()
+        );
+
+        assert!(
+            repl::execute_line(format!("config -c {} --tar true --gzip true", config_path)).is_ok()
+        );
+        assert!(repl::execute_line(format!("backup -c {}", config_path)).is_ok());
+        assert!(dest.join("backup.tar.gz").exists());
+        let leftovers = fs::read_dir(&dest)
+            .unwrap()
+            .filter(|e| {
+                let name = e.as_ref().unwrap().file_name();
+                name.to_string_lossy().starts_with(".babycancer-tmp-")
+            })
+            .count();
+        assert_eq!(leftovers, 0);
+
+        let _ = fs::remove_dir_all(&dest);
+    });
+}
+
+#[test]
+fn test_resume_backup() {
+    with_clean_config("test_resume_backup", |config_path| {
+        let dest = PathBuf::from("tests/example/resume_dest");
+        let _ = fs::remove_dir_all(&dest);
+        assert!(
+            repl::execute_line(format!(
+                "config -c {} --dest-path {}",
+                config_path,
+                dest.display()
+            ))
+            .is_ok()
+        );
+        assert!(repl::execute_line(format!("backup -c {}", config_path)).is_ok());
+        assert!(!dest.join(journal::JOURNAL_FILE_NAME).exists());
+
+        // Pretend the run was interrupted after everything but my_pic.png
+        let manifest = manifest::Manifest::load(&dest).unwrap();
+        let journal: String = manifest
+            .entries
+            .iter()
+            .filter(|entry| entry.path != Path::new("my_pic.png"))
+            .map(|entry| serde_json::to_string(entry).unwrap() + "\n")
+            .collect();
+        let header = serde_json::json!({ "algorithm": manifest.algorithm }).to_string();
+        fs::write(dest.join(journal::JOURNAL_FILE_NAME), header + "\n" + &journal).unwrap();
+        fs::remove_file(dest.join("my_pic.png")).unwrap();
+        fs::write(dest.join("my_test.txt"), "not copied again").unwrap();
+
+        let events = run_json(&["backup", "-c", &config_path, "--resume"]);
+        let copied: Vec<_> = events
+            .iter()
+            .filter(|e| e["event"] == "file_copied")
+            .map(|e| e["source"].as_str().unwrap())
+            .collect();
+        assert_eq!(copied, ["tests/example/src/my_pic.png"]);
+        let summary = events.iter().find(|e| e["event"] == "summary").unwrap();
+        assert_eq!(summary["files_resumed"], 3);
+        assert!(dest.join("my_pic.png").exists());
+        assert_eq!(
+            fs::read_to_string(dest.join("my_test.txt")).unwrap(),
+            "not copied again"
+        );
+        assert!(!dest.join(journal::JOURNAL_FILE_NAME).exists());
+        assert_eq!(manifest::Manifest::load(&dest).unwrap().entries.len(), 4);
+
+        // Without --resume everything is copied again
+        assert!(repl::execute_line(format!("backup -c {}", config_path)).is_ok());
+        assert_eq!(
+            fs::read(dest.join("my_test.txt")).unwrap(),
+            fs::read("tests/example/src/my_test.txt").unwrap()
+        );
+
+        let _ = fs::remove_dir_all(&dest);
+    });
+}
+
+#[test]
+fn test_resume_other_hash() {
+    with_clean_config("test_resume_other_hash", |config_path| {
+        let dest = PathBuf::from("tests/example/resume_hash_dest");
+        let _ = fs::remove_dir_all(&dest);
+        assert!(
+            repl::execute_line(format!(
+                "config -c {} --dest-path {} --hash crc32",
+                config_path,
+                dest.display()
+            ))
+            .is_ok()
+        );
+        assert!(repl::execute_line(format!("backup -c {}", config_path)).is_ok());
+
+        // Interrupt the run after everything, then switch to another algorithm
+        let manifest = manifest::Manifest::load(&dest).unwrap();
+        let journal: String = manifest
+            .entries
+            .iter()
+            .map(|entry| serde_json::to_string(entry).unwrap() + "\n")
+            .collect();
+        let header = serde_json::json!({ "algorithm": manifest.algorithm }).to_string();
+        fs::write(dest.join(journal::JOURNAL_FILE_NAME), header + "\n" + &journal).unwrap();
+        assert!(repl::execute_line(format!("config -c {} --hash sha256", config_path)).is_ok());
+
+        let events = run_json(&["backup", "-c", &config_path, "--resume"]);
+        let summary = events.iter().find(|e| e["event"] == "summary").unwrap();
+        assert_eq!(summary["files_resumed"], 0);
+        assert!(repl::execute_line(format!("verify -c {}", config_path)).is_ok());
+
+        // A journal without a header is discarded as well
+        fs::write(dest.join(journal::JOURNAL_FILE_NAME), journal).unwrap();
+        let events = run_json(&["backup", "-c", &config_path, "--resume"]);
+        let summary = events.iter().find(|e| e["event"] == "summary").unwrap();
+        assert_eq!(summary["files_resumed"], 0);
+
+        let _ = fs::remove_dir_all(&dest);
+    });
+}
+
+#[test]
+fn test_sparse_files() {
+    use std::io::{Seek, SeekFrom, Write};
+
+    with_clean_config("test_sparse_files", |config_path| {
+        let source = PathBuf::from("tests/example/sparse_src");
+        let dest = PathBuf::from("tests/example/sparse_dest");
+        let _ = fs::remove_dir_all(&source);
+        let _ = fs::remove_dir_all(&dest);
+        fs::create_dir_all(&source).unwrap();
+
+        // 8 MiB with data at 1 MiB and 3 MiB, ending with a hole, plus a file
+        // with more data segments than fit in a single GNU sparse header
+        let mut file = File::create(source.join("disk.img")).unwrap();
+        file.set_len(8 << 20).unwrap();
+        for offset in [1 << 20, 3 << 20] {
+            file.seek(SeekFrom::Start(offset)).unwrap();
+            file.write_all(&[0xab; 10000]).unwrap();
+        }
+        let mut file = File::create(source.join("many.img")).unwrap();
+        file.set_len(40 << 16).unwrap();
+        for segment in 0..30 {
+            file.seek(SeekFrom::Start(segment << 16)).unwrap();
+            file.write_all(format!("segment {}", segment).as_bytes()).unwrap();
+        }
+        drop(file);
+
+        assert!(
+            repl::execute_line(format!(
+                "config -c {} --source-path {} --dest-path {}",
+                config_path,
+                source.display(),
+                dest.display()
+            ))
+            .is_ok()
+        );
+        let events = run_json(&["backup", "-c", &config_path]);
+        let summary = events.iter().find(|e| e["event"] == "summary").unwrap();
+        assert_eq!(summary["bytes_copied"], (8 << 20) + (40 << 16));
+        assert!(summary["bytes_allocated"].as_u64().unwrap() < 1 << 20);
+
+        for name in ["disk.img", "many.img"] {
+            let copy = fs::metadata(dest.join(name)).unwrap();
+            assert!(copy.blocks() * 512 < copy.len() / 4, "{} should stay sparse", name);
+            assert_eq!(
+                fs::read(dest.join(name)).unwrap(),
+                fs::read(source.join(name)).unwrap()
+            );
+        }
+
+        // Archives store the holes as GNU sparse entries
+        assert!(
+            repl::execute_line(format!("config -c {} --tar true --gzip false", config_path))
+                .is_ok()
+        );
+        assert!(repl::execute_line(format!("backup -c {}", config_path)).is_ok());
+        assert!(fs::metadata(dest.join("backup.tar")).unwrap().len() < 1 << 20);
+        assert!(repl::execute_line(format!("verify -c {}", config_path)).is_ok());
+
+        let mut archive = tar::Archive::new(File::open(dest.join("backup.tar")).unwrap());
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let path = entry.path().unwrap().into_owned();
+            let mut contents = Vec::new();
+            entry.read_to_end(&mut contents).unwrap();
+            assert_eq!(contents, fs::read(source.join(path)).unwrap());
+        }
+
+        let _ = fs::remove_dir_all(&source);
+        let _ = fs::remove_dir_all(&dest);
+    });
+}
+
+#[test]
+fn test_hard_links() {
+    with_clean_config("test_hard_links", |config_path| {
+        let source = PathBuf::from("tests/example/links_src");
+        let dest = PathBuf::from("tests/example/links_dest");
+        let _ = fs::remove_dir_all(&source);
+        let _ = fs::remove_dir_all(&dest);
+        fs::create_dir_all(source.join("sub")).unwrap();
+        fs::write(source.join("a.txt"), "shared").unwrap();
+        fs::hard_link(source.join("a.txt"), source.join("sub/b.txt")).unwrap();
+        fs::write(source.join("c.txt"), "shared").unwrap();
+
+        assert!(
+            repl::execute_line(format!(
+                "config -c {} --source-path {} --dest-path {} --jobs 4",
+                config_path,
+                source.display(),
+                dest.display()
+            ))
+            .is_ok()
+        );
+        let events = run_json(&["backup", "-c", &config_path]);
+        let summary = events.iter().find(|e| e["event"] == "summary").unwrap();
+        assert_eq!(summary["files_copied"], 2);
+        assert_eq!(summary["hard_links"], 1);
+
+        let inode = |path: &str| fs::metadata(dest.join(path)).unwrap().ino();
+        assert_eq!(inode("a.txt"), inode("sub/b.txt"));
+        assert_ne!(inode("a.txt"), inode("c.txt"));
+        assert!(repl::execute_line(format!("verify -c {}", config_path)).is_ok());
+
+        // A second run replaces the links instead of failing on them
+        assert!(repl::execute_line(format!("backup -c {}", config_path)).is_ok());
+        assert_eq!(inode("a.txt"), inode("sub/b.txt"));
+
+        assert!(
+            repl::execute_line(format!("config -c {} --tar true --gzip false", config_path))
+                .is_ok()
+        );
+        assert!(repl::execute_line(format!("backup -c {}", config_path)).is_ok());
+        assert!(repl::execute_line(format!("verify -c {}", config_path)).is_ok());
+
+        let mut archive = tar::Archive::new(File::open(dest.join("backup.tar")).unwrap());
+        let links: Vec<_> = archive
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap())
+            .filter(|entry| entry.header().entry_type() == tar::EntryType::Link)
+            .map(|entry| {
+                (
+                    entry.path().unwrap().into_owned(),
+                    entry.link_name().unwrap().unwrap().into_owned(),
+                )
+            })
+            .collect();
+        // Whichever name is walked first holds the data
+        let (a, b) = (PathBuf::from("a.txt"), PathBuf::from("sub/b.txt"));
+        assert!(links == [(b.clone(), a.clone())] || links == [(a, b)]);
+
+        let _ = fs::remove_dir_all(&source);
+        let _ = fs::remove_dir_all(&dest);
+    });
+}
+
+#[test]
+fn test_reflink_fallback() {
+    with_clean_config("test_reflink", |config_path| {
+        let dest = PathBuf::from("tests/example/reflink_dest");
+        let _ = fs::remove_dir_all(&dest);
+        assert!(
+            repl::execute_line(format!(
+                "config -c {} --dest-path {} --reflink true",
+                config_path,
+                dest.display()
+            ))
+            .is_ok()
+        );
+        let config = config::get_config(&Some(PathBuf::from(&config_path))).unwrap();
+        assert!(config.copy_config.reflink);
+
+        // Whether or not the file system can clone files, the copies are complete
+        let events = run_json(&["backup", "-c", &config_path]);
+        let summary = events.iter().find(|e| e["event"] == "summary").unwrap();
+        assert_eq!(summary["files_copied"], 3);
+        let reflinked = events
+            .iter()
+            .filter(|e| e["event"] == "file_copied" && e["reflinked"] == true)
+            .count();
+        assert_eq!(summary["files_reflinked"], reflinked);
+        assert_eq!(
+            fs::read(dest.join("my_pic.png")).unwrap(),
+            fs::read("tests/example/src/my_pic.png").unwrap()
+        );
+        assert!(repl::execute_line(format!("verify -c {}", config_path)).is_ok());
+
+        assert!(repl::execute_line(format!("reset -c {} --reflink", config_path)).is_ok());
+        let config = config::get_config(&Some(PathBuf::from(&config_path))).unwrap();
+        assert!(!config.copy_config.reflink);
+
+        let _ = fs::remove_dir_all(&dest);
+    });
+}
+
+#[test]
+fn test_symlink_policies() {
+    use std::os::unix::fs::symlink;
+
+    with_clean_config("test_symlinks", |config_path| {
+        let source = PathBuf::from("tests/example/symlinks_src");
+        let dest = PathBuf::from("tests/example/symlinks_dest");
+        let _ = fs::remove_dir_all(&source);
+        fs::create_dir_all(source.join("dir")).unwrap();
+        fs::write(source.join("file.txt"), "data").unwrap();
+        fs::write(source.join("dir/inner.txt"), "inner").unwrap();
+        let absolute = std::path::absolute(source.join("file.txt")).unwrap();
+        symlink(&absolute, source.join("abs_link")).unwrap();
+        symlink("file.txt", source.join("rel_link")).unwrap();
+        symlink("/nonexistent/outside", source.join("outside_link")).unwrap();
+        symlink("dir", source.join("dir_link")).unwrap();
+        symlink("..", source.join("dir/back")).unwrap();
+
+        let backup = |policy: &str| {
+            let _ = fs::remove_dir_all(&dest);
+            assert!(
+                repl::execute_line(format!(
+                    "config -c {} --source-path {} --dest-path {} --symlinks {}",
+                    config_path,
+                    source.display(),
+                    dest.display(),
+                    policy
+                ))
+                .is_ok()
+            );
+            repl::execute_line(format!("backup -c {}", config_path))
+        };
+        let link = |path: &str| fs::read_link(dest.join(path)).unwrap();
+
+        // Links are kept as they are, and links to directories are not walked
+        assert!(backup("preserve").is_ok());
+        assert_eq!(link("abs_link"), absolute);
+        assert_eq!(link("rel_link"), Path::new("file.txt"));
+        assert_eq!(link("outside_link"), Path::new("/nonexistent/outside"));
+        assert_eq!(link("dir_link"), Path::new("dir"));
+        assert_eq!(link("dir/back"), Path::new(".."));
+
+        // Links into the source point inside the backup instead
+        assert!(backup("rewrite").is_ok());
+        assert_eq!(link("abs_link"), Path::new("file.txt"));
+        assert_eq!(link("rel_link"), Path::new("file.txt"));
+        assert_eq!(link("outside_link"), Path::new("/nonexistent/outside"));
+        assert_eq!(fs::read_to_string(dest.join("abs_link")).unwrap(), "data");
+
+        assert!(backup("skip").is_ok());
+        assert!(fs::symlink_metadata(dest.join("rel_link")).is_err());
+        assert!(dest.join("file.txt").exists());
+
+        // Following copies what links point to, and stops at cycles and
+        // broken links instead of looping
+        let result = backup("follow");
+        assert!(matches!(result, Err(Error::Partial { failed: 3 })));
+        let abs_copy = fs::symlink_metadata(dest.join("abs_link")).unwrap();
+        assert!(abs_copy.is_file());
+        assert_eq!(
+            fs::read_to_string(dest.join("dir_link/inner.txt")).unwrap(),
+            "inner"
+        );
+        assert!(!dest.join("dir/back").exists());
+
+        // Archives use the same policy
+        assert!(
+            repl::execute_line(format!("config -c {} --tar true --gzip false", config_path))
+                .is_ok()
+        );
+        assert!(backup("rewrite").is_ok());
+        let mut archive = tar::Archive::new(File::open(dest.join("backup.tar")).unwrap());
+        let abs_entry = archive
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap())
+            .find(|entry| entry.path().unwrap() == Path::new("abs_link"))
+            .unwrap();
+        assert_eq!(abs_entry.header().entry_type(), tar::EntryType::Symlink);
+        assert_eq!(
+            abs_entry.link_name().unwrap().unwrap(),
+            Path::new("file.txt")
+        );
+
+        let _ = fs::remove_dir_all(&source);
+        let _ = fs::remove_dir_all(&dest);
+    });
+}
+
+#[test]
+fn test_mount_points() {
+    use std::os::unix::fs::symlink;
+
+    with_clean_config("test_mounts", |config_path| {
+        let source = PathBuf::from("tests/example/mounts_src");
+        let dest = PathBuf::from("tests/example/mounts_dest");
+        let _ = fs::remove_dir_all(&source);
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("file.txt"), "data").unwrap();
+        symlink("/proc", source.join("proc")).unwrap();
+
+        // /dev/shm is a separate tmpfs mount on most Linux systems
+        let root_dev = fs::metadata(&source).unwrap().dev();
+        let shm = fs::metadata("/dev/shm").is_ok_and(|m| m.dev() != root_dev);
+        if shm {
+            symlink("/dev/shm", source.join("shm")).unwrap();
+        }
+
+        let backup = |options: &str| {
+            let _ = fs::remove_dir_all(&dest);
+            assert!(
+                repl::execute_line(format!(
+                    "config -c {} --source-path {} --dest-path {} --symlinks follow {}",
+                    config_path,
+                    source.display(),
+                    dest.display(),
+                    options
+                ))
+                .is_ok()
+            );
+            repl::execute_line(format!("backup -c {}", config_path))
+        };
+
+        // Pseudo file systems are never walked
+        let _ = backup("");
+        assert!(dest.join("file.txt").exists());
+        assert!(!dest.join("proc").exists());
+
+        if shm {
+            assert!(backup("--skip-tmpfs true").is_ok());
+            assert!(!dest.join("shm").exists());
+
+            assert!(backup("--skip-tmpfs false --one-file-system true").is_ok());
+            assert!(!dest.join("shm").exists());
+            assert!(dest.join("file.txt").exists());
+        }
+
+        let _ = fs::remove_dir_all(&source);
+        let _ = fs::remove_dir_all(&dest);
+    });
+}
+
+#[test]
+fn test_empty_directories() {
+    use std::os::unix::fs::PermissionsExt;
+    use std::time::{Duration, SystemTime};
+
+    with_clean_config("test_empty_dirs", |config_path| {
+        let source = PathBuf::from("tests/example/empty_dirs_src");
+        let dest = PathBuf::from("tests/example/empty_dirs_dest");
+        let unlock = |root: &Path| {
+            if let Ok(metadata) = fs::metadata(root.join("locked")) {
+                let mut permissions = metadata.permissions();
+                permissions.set_mode(0o755);
+                fs::set_permissions(root.join("locked"), permissions).unwrap();
+            }
+        };
+        unlock(&source);
+        unlock(&dest);
+        let _ = fs::remove_dir_all(&source);
+        let _ = fs::remove_dir_all(&dest);
+        fs::create_dir_all(source.join("empty/nested")).unwrap();
+        fs::create_dir_all(source.join("locked")).unwrap();
+        fs::write(source.join("locked/file.txt"), "data").unwrap();
+        let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        fs::File::open(source.join("locked"))
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+        fs::set_permissions(source.join("locked"), fs::Permissions::from_mode(0o555)).unwrap();
+
+        let backup = |keep: bool| {
+            assert!(
+                repl::execute_line(format!(
+                    "config -c {} --source-path {} --dest-path {} --keep-empty-dirs {}",
+                    config_path,
+                    source.display(),
+                    dest.display(),
+                    keep
+                ))
+                .is_ok()
+            );
+            repl::execute_line(format!("backup -c {}", config_path))
+        };
+
+        // Empty directories are only created when asked for
+        assert!(backup(false).is_ok());
+        assert!(!dest.join("empty").exists());
+        assert!(backup(true).is_ok());
+        assert!(dest.join("empty/nested").is_dir());
+
+        // Directories get the mode and times of their source, even over an
+        // earlier read-only copy
+        let locked = fs::metadata(dest.join("locked")).unwrap();
+        assert_eq!(locked.permissions().mode() & 0o777, 0o555);
+        assert_eq!(locked.modified().unwrap(), mtime);
+        assert_eq!(
+            fs::read_to_string(dest.join("locked/file.txt")).unwrap(),
+            "data"
+        );
+
+        unlock(&source);
+        unlock(&dest);
+        let _ = fs::remove_dir_all(&source);
+        let _ = fs::remove_dir_all(&dest);
+    });
+}
+
+#[test]
+fn test_acls_and_capabilities() {
+    let source = PathBuf::from("tests/example/xattrs_src");
+    let dest = PathBuf::from("tests/example/xattrs_dest");
+    let _ = fs::remove_dir_all(&source);
+    let _ = fs::remove_dir_all(&dest);
+    fs::create_dir_all(source.join("shared")).unwrap();
+    fs::write(source.join("tool"), "binary").unwrap();
+
+    // CAP_NET_BIND_SERVICE, permitted and effective
+    let mut capability = vec![];
+    for word in [0x0200_0001u32, 1 << 10, 0, 0, 0] {
+        capability.extend_from_slice(&word.to_le_bytes());
+    }
+    // user::rwx, user:1000:rwx, group::r-x, mask::rwx, other::r-x
+    let mut acl = 2u32.to_le_bytes().to_vec();
+    let entries = [
+        (1u16, 7u16, u32::MAX),
+        (2, 7, 1000),
+        (4, 5, u32::MAX),
+        (0x10, 7, u32::MAX),
+        (0x20, 5, u32::MAX),
+    ];
+    for (tag, perm, id) in entries {
+        acl.extend_from_slice(&tag.to_le_bytes());
+        acl.extend_from_slice(&perm.to_le_bytes());
+        acl.extend_from_slice(&id.to_le_bytes());
+    }
+    let tool = source.join("tool");
+    let shared = source.join("shared");
+    if xattr::set(&tool, "security.capability", &capability).is_err()
+        || xattr::set(&shared, "system.posix_acl_default", &acl).is_err()
+    {
+        // Needs root and a file system with attributes
+        let _ = fs::remove_dir_all(&source);
+        return;
+    }
+    fs::write(shared.join("notes.txt"), "notes").unwrap();
+    let file_acl = xattr::get(shared.join("notes.txt"), "system.posix_acl_access").unwrap();
+    assert!(file_acl.is_some());
+
+    with_clean_config("test_xattrs", |config_path| {
+        let backup = |tar: bool| {
+            let _ = fs::remove_dir_all(&dest);
+            assert!(
+                repl::execute_line(format!(
+                    "config -c {} --source-path {} --dest-path {} --tar {} --keep-empty-dirs true",
+                    config_path,
+                    source.display(),
+                    dest.display(),
+                    tar
+                ))
+                .is_ok()
+            );
+            assert!(repl::execute_line(format!("backup -c {}", config_path)).is_ok());
+        };
+        backup(false);
+        let get = |path: &str, name: &str| xattr::get(dest.join(path), name).unwrap();
+        assert_eq!(get("tool", "security.capability"), Some(capability.clone()));
+        assert_eq!(get("shared", "system.posix_acl_default"), Some(acl.clone()));
+        assert_eq!(get("shared/notes.txt", "system.posix_acl_access"), file_acl);
+
+        // Archives carry them in PAX headers
+        backup(true);
+        let mut archive = tar::Archive::new(fs::File::open(dest.join("backup.tar")).unwrap());
+        let mut found = Vec::new();
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let path = entry.path().unwrap().into_owned();
+            for extension in entry.pax_extensions().unwrap().into_iter().flatten() {
+                let extension = extension.unwrap();
+                let key = extension.key().unwrap().to_string();
+                found.push((path.clone(), key, extension.value_bytes().to_vec()));
+            }
+        }
+        let expected = [
+            ("tool", "security.capability", &capability),
+            ("shared", "system.posix_acl_default", &acl),
+            ("shared/notes.txt", "system.posix_acl_access", file_acl.as_ref().unwrap()),
+        ];
+        for (path, name, value) in expected {
+            let key = format!("SCHILY.xattr.{}", name);
+            assert!(found.contains(&(PathBuf::from(path), key, value.clone())));
+        }
+    });
+
+    let _ = fs::remove_dir_all(&source);
+    let _ = fs::remove_dir_all(&dest);
+}
+
+#[test]
+fn test_sockets() {
+    use std::os::unix::net::UnixListener;
+
+    with_clean_config("test_sockets", |config_path| {
+        let source = PathBuf::from("tests/example/sockets_src");
+        let dest = PathBuf::from("tests/example/sockets_dest");
+        let _ = fs::remove_dir_all(&source);
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("file.txt"), "data").unwrap();
+        let _listener = UnixListener::bind(source.join("app.sock")).unwrap();
+
+        let backup = |options: &str| {
+            let _ = fs::remove_dir_all(&dest);
+            fs::create_dir_all(&dest).unwrap();
+            assert!(
+                repl::execute_line(format!(
+                    "config -c {} --source-path {} --dest-path {} {}",
+                    config_path,
+                    source.display(),
+                    dest.display(),
+                    options
+                ))
+                .is_ok()
+            );
+            let events = run_json(&["backup", "-c", &config_path]);
+            let recorded: Vec<_> = events
+                .iter()
+                .filter(|e| e["event"] == "metadata_recorded")
+                .collect();
+            assert_eq!(recorded.len(), 1);
+            assert_eq!(recorded[0]["kind"], "socket");
+            let summary = events.iter().find(|e| e["event"] == "summary").unwrap();
+            assert_eq!(summary["metadata_only"], 1);
+            assert_eq!(summary["failures"].as_array().unwrap().len(), 0);
+
+            let manifest = fs::read_to_string(dest.join("backup-manifest.json")).unwrap();
+            let manifest: serde_json::Value = serde_json::from_str(&manifest).unwrap();
+            let entries = manifest["entries"].as_array().unwrap();
+            let socket = entries.iter().find(|e| e["path"] == "app.sock").unwrap();
+            assert_eq!(socket["kind"], "socket");
+            assert!(socket["hash"].is_null());
+
+            let verified = run_json(&["verify", "-c", &config_path]);
+            let summary = verified.iter().find(|e| e["event"] == "verify_summary").unwrap();
+            assert_eq!(summary["missing"], 0);
+            assert_eq!(summary["extra"], 0);
+            recorded[0]["placeholder"].clone()
+        };
+
+        // Only recorded by default, in copies and archives alike
+        assert!(backup("--tar false --placeholders false").is_null());
+        assert!(fs::symlink_metadata(dest.join("app.sock")).is_err());
+        assert!(dest.join("file.txt").exists());
+        assert!(backup("--tar true").is_null());
+
+        let placeholder = backup("--tar false --placeholders true");
+        assert_eq!(placeholder, dest.join("app.sock").to_str().unwrap());
+        let metadata = fs::symlink_metadata(dest.join("app.sock")).unwrap();
+        assert!(metadata.file_type().is_socket());
+
+        let _ = fs::remove_dir_all(&source);
+        let _ = fs::remove_dir_all(&dest);
+    });
+}
+
+#[test]
+fn test_device_nodes() {
+    use nix::sys::stat::{Mode, SFlag, major, makedev, minor, mknod};
+
+    with_clean_config("test_devices", |config_path| {
+        let source = PathBuf::from("tests/example/devices_src");
+        let dest = PathBuf::from("tests/example/devices_dest");
+        let _ = fs::remove_dir_all(&source);
+        let _ = fs::remove_dir_all(&dest);
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("file.txt"), "data").unwrap();
+        // Numbers that do not fit the old 16-bit dev_t encoding
+        let node = source.join("node");
+        if mknod(&node, SFlag::S_IFCHR, Mode::S_IRUSR, makedev(300, 70000)).is_err() {
+            // Needs root
+            let _ = fs::remove_dir_all(&source);
+            return;
+        }
+        assert!(
+            repl::execute_line(format!(
+                "config -c {} --source-path {} --dest-path {}",
+                config_path,
+                source.display(),
+                dest.display()
+            ))
+            .is_ok()
+        );
+        let manifest_entry = || {
+            let manifest = fs::read_to_string(dest.join("backup-manifest.json")).unwrap();
+            let manifest: serde_json::Value = serde_json::from_str(&manifest).unwrap();
+            let entries = manifest["entries"].as_array().unwrap().clone();
+            let entry = entries.into_iter().find(|e| e["path"] == "node").unwrap();
+            assert_eq!(entry["device"]["major"], 300);
+            assert_eq!(entry["device"]["minor"], 70000);
+            entry
+        };
+
+        assert!(repl::execute_line(format!("backup -c {}", config_path)).is_ok());
+        let rdev = fs::symlink_metadata(dest.join("node")).unwrap().rdev();
+        assert_eq!((major(rdev), minor(rdev)), (300, 70000));
+        assert_eq!(manifest_entry()["metadata_only"], false);
+
+        // Without the privilege to create device nodes, they are only
+        // recorded and the run still succeeds
+        let _ = fs::remove_dir_all(&dest);
+        let unprivileged = std::process::Command::new("setpriv")
+            .arg("--bounding-set=-mknod")
+            .arg(env!("CARGO_BIN_EXE_babycancer"))
+            .args(["backup", "-c", &config_path])
+            .status();
+        if let Ok(status) = unprivileged {
+            assert!(status.success());
+            assert!(fs::symlink_metadata(dest.join("node")).is_err());
+            assert!(dest.join("file.txt").exists());
+            assert_eq!(manifest_entry()["metadata_only"], true);
+
+            let verified = run_json(&["verify", "-c", &config_path]);
+            let summary = verified.iter().find(|e| e["event"] == "verify_summary").unwrap();
+            assert_eq!(summary["missing"], 0);
+        }
+
+        let _ = fs::remove_dir_all(&source);
+        let _ = fs::remove_dir_all(&dest);
+    });
+}
+
+#[test]
+fn test_depth_and_markers() {
+    with_clean_config("test_markers", |config_path| {
+        let source = PathBuf::from("tests/example/markers_src");
+        let dest = PathBuf::from("tests/example/markers_dest");
+        let _ = fs::remove_dir_all(&source);
+        fs::create_dir_all(source.join("a/b/c")).unwrap();
+        fs::create_dir_all(source.join("opted_out")).unwrap();
+        fs::create_dir_all(source.join("cache")).unwrap();
+        fs::create_dir_all(source.join("not_a_cache")).unwrap();
+        fs::write(source.join("top.txt"), "top").unwrap();
+        fs::write(source.join("a/mid.txt"), "mid").unwrap();
+        fs::write(source.join("a/b/c/deep.txt"), "deep").unwrap();
+        fs::write(source.join("opted_out/.nobackup"), "").unwrap();
+        fs::write(source.join("opted_out/secret.txt"), "secret").unwrap();
+        fs::write(
+            source.join("cache/CACHEDIR.TAG"),
+            "Signature: 8a477f597d28d172789f06886806bc55\n# This is a cache\n",
+        )
+        .unwrap();
+        fs::write(source.join("cache/blob"), "blob").unwrap();
+        // Without the signature, the tag does not count
+        fs::write(source.join("not_a_cache/CACHEDIR.TAG"), "hello").unwrap();
+        fs::write(source.join("not_a_cache/data"), "data").unwrap();
+
+        let backup = |options: &str| {
+            let _ = fs::remove_dir_all(&dest);
+            assert!(
+                repl::execute_line(format!(
+                    "config -c {} --source-path {} --dest-path {} {}",
+                    config_path,
+                    source.display(),
+                    dest.display(),
+                    options
+                ))
+                .is_ok()
+            );
+            assert!(repl::execute_line(format!("backup -c {}", config_path)).is_ok());
+        };
+
+        backup("");
+        assert!(dest.join("a/b/c/deep.txt").exists());
+        assert!(!dest.join("opted_out").exists());
+        assert!(!dest.join("cache").exists());
+        assert!(dest.join("not_a_cache/data").exists());
+
+        backup("--max-depth 2");
+        assert!(dest.join("top.txt").exists());
+        assert!(dest.join("a/mid.txt").exists());
+        assert!(!dest.join("a/b/c").exists());
+
+        // Archives leave out the same entries
+        backup("--tar true");
+        let mut archive = tar::Archive::new(fs::File::open(dest.join("backup.tar")).unwrap());
+        let paths: Vec<PathBuf> = archive
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap().path().unwrap().into_owned())
+            .collect();
+        assert!(paths.contains(&PathBuf::from("a/b")));
+        assert!(!paths.contains(&PathBuf::from("a/b/c")));
+        assert!(!paths.iter().any(|path| path.starts_with("cache")));
+
+        assert!(repl::execute_line(format!("reset -c {} --max-depth", config_path)).is_ok());
+        backup("--tar false --exclude-markers CACHEDIR.TAG");
+        assert!(dest.join("a/b/c/deep.txt").exists());
+        assert!(dest.join("opted_out/secret.txt").exists());
+        assert!(!dest.join("cache").exists());
+
+        let _ = fs::remove_dir_all(&source);
+        let _ = fs::remove_dir_all(&dest);
+    });
+}
+
+#[test]
+fn test_ignore_files() {
+    with_clean_config("test_ignore_files", |config_path| {
+        let source = PathBuf::from("tests/example/ignore_src");
+        let dest = PathBuf::from("tests/example/ignore_dest");
+        let _ = fs::remove_dir_all(&source);
+        let files = [
+            "target/out.bin",
+            "a.o",
+            "keep.o",
+            "build/x",
+            "sub/build/y",
+            "sub/x.log",
+            "sub/important.log",
+            "x.log",
+            "docs/draft.md",
+            "docs/a/b/draft.md",
+            "docs/readme.md",
+            "deep/z.o",
+            "ab1.dat",
+            "abx.dat",
+        ];
+        for file in files {
+            let path = source.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, file).unwrap();
+        }
+        let gitignore = "# build output\ntarget/\n*.o\n!keep.o\n/build\ndocs/**/draft.md\nab[0-9].dat\n";
+        fs::write(source.join(".gitignore"), gitignore).unwrap();
+        fs::write(source.join("sub/.ignore"), "*.log\n").unwrap();
+        fs::write(source.join("sub/.babycancerignore"), "!important.log\n").unwrap();
+        fs::write(source.join("deep/.gitignore"), "!*.o\n").unwrap();
+
+        let backup = |ignore_files: bool| {
+            let _ = fs::remove_dir_all(&dest);
+            assert!(
+                repl::execute_line(format!(
+                    "config -c {} --source-path {} --dest-path {} --ignore-files {}",
+                    config_path,
+                    source.display(),
+                    dest.display(),
+                    ignore_files
+                ))
+                .is_ok()
+            );
+            assert!(repl::execute_line(format!("backup -c {}", config_path)).is_ok());
+        };
+
+        backup(false);
+        for file in files {
+            assert!(dest.join(file).exists(), "{} should be backed up", file);
+        }
+
+        backup(true);
+        let ignored = [
+            "target/out.bin",
+            "a.o",
+            "build/x",
+            "sub/x.log",
+            "docs/draft.md",
+            "docs/a/b/draft.md",
+            "ab1.dat",
+        ];
+        for file in files {
+            assert_eq!(
+                dest.join(file).exists(),
+                !ignored.contains(&file),
+                "{} should {}be backed up",
+                file,
+                if ignored.contains(&file) { "not " } else { "" }
+            );
+        }
+        assert!(dest.join(".gitignore").exists());
+
+        let _ = fs::remove_dir_all(&source);
+        let _ = fs::remove_dir_all(&dest);
+    });
+}
+
+#[test]
+fn test_non_utf8_names() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    with_clean_config("test_non_utf8", |config_path| {
+        let source = PathBuf::from("tests/example/non_utf8_src");
+        let dest = PathBuf::from("tests/example/non_utf8_dest");
+        let _ = fs::remove_dir_all(&source);
+        // "café.txt" in Latin-1, inside a directory named with invalid bytes
+        let name = OsStr::from_bytes(b"caf\xe9.txt");
+        let dir = source.join(OsStr::from_bytes(b"dir\xff"));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(source.join(name), "latin-1").unwrap();
+        fs::write(dir.join("inner.txt"), "inner").unwrap();
+        fs::write(source.join(OsStr::from_bytes(b"caf\xe9.tmp")), "temp").unwrap();
+        fs::write(source.join("naïve.log"), "log").unwrap();
+        fs::write(source.join(".gitignore"), "caf?.tmp\nnaïve.log\n").unwrap();
+
+        let backup = |options: &str| {
+            let _ = fs::remove_dir_all(&dest);
+            assert!(
+                repl::execute_line(format!(
+                    "config -c {} --source-path {} --dest-path {} {}",
+                    config_path,
+                    source.display(),
+                    dest.display(),
+                    options
+                ))
+                .is_ok()
+            );
+            run_json(&["backup", "-c", &config_path])
+        };
+
+        // Names are matched on their bytes
+        let events = backup("--file-name (?-u:\\xe9) --ignore-files true");
+        assert!(dest.join(name).exists());
+        assert!(!dest.join("naïve.log").exists());
+        assert!(!dest.join(OsStr::from_bytes(b"caf\xe9.tmp")).exists());
+        let copied = events.iter().find(|e| e["event"] == "file_copied").unwrap();
+        assert_eq!(copied["source"]["lossy"], format!("{}/caf\u{FFFD}.txt", source.display()));
+        let bytes: Vec<u8> = copied["source"]["bytes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|b| b.as_u64().unwrap() as u8)
+            .collect();
+        assert_eq!(Path::new(OsStr::from_bytes(&bytes)), source.join(name));
+
+        // Or on their lossy form
+        backup("--file-name \u{FFFD} --name-matching lossy --ignore-files false");
+        assert!(dest.join(name).exists());
+        assert!(!dest.join(OsStr::from_bytes(b"dir\xff/inner.txt")).exists());
+
+        // The manifest keeps the exact names, for copies and archives alike
+        assert!(repl::execute_line(format!("reset -c {} --file-name", config_path)).is_ok());
+        for tar in ["false", "true"] {
+            let events = backup(&format!("--tar {}", tar));
+            let summary = events.iter().find(|e| e["event"] == "summary").unwrap();
+            assert_eq!(summary["failures"].as_array().unwrap().len(), 0);
+
+            let verified = run_json(&["verify", "-c", &config_path]);
+            let summary = verified.iter().find(|e| e["event"] == "verify_summary").unwrap();
+            assert_eq!(summary["checked"], 5);
+            assert_eq!(summary["missing"], 0);
+            assert_eq!(summary["extra"], 0);
+            assert_eq!(summary["corrupted"], 0);
+
+            let diff = run_json(&["diff", "-c", &config_path]);
+            let summary = diff.iter().find(|e| e["event"] == "diff_summary").unwrap();
+            assert_eq!(summary["unchanged"], 5);
+        }
+
+        let _ = fs::remove_dir_all(&source);
+        let _ = fs::remove_dir_all(&dest);
+    });
+}
+
+#[test]
+fn test_changing_files() {
+    use std::os::unix::fs::FileExt;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    with_clean_config("test_changing", |config_path| {
+        let source = PathBuf::from("tests/example/changing_src");
+        let dest = PathBuf::from("tests/example/changing_dest");
+        let _ = fs::remove_dir_all(&source);
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("stable.txt"), "stable").unwrap();
+        let busy = source.join("busy.log");
+        fs::write(&busy, vec![b'x'; 32 << 20]).unwrap();
+
+        // Keep rewriting the start of the file for as long as backups run
+        let stop = Arc::new(AtomicBool::new(false));
+        let writer = {
+            let file = fs::OpenOptions::new().write(true).open(&busy).unwrap();
+            let stop = stop.clone();
+            std::thread::spawn(move || {
+                let mut counter = 0u64;
+                while !stop.load(Ordering::Relaxed) {
+                    counter += 1;
+                    file.write_all_at(&counter.to_le_bytes(), 0).unwrap();
+                    std::thread::sleep(std::time::Duration::from_micros(200));
+                }
+            })
+        };
+
+        let backup = |tar: bool| {
+            let _ = fs::remove_dir_all(&dest);
+            fs::create_dir_all(&dest).unwrap();
+            assert!(
+                repl::execute_line(format!(
+                    "config -c {} --source-path {} --dest-path {} --tar {} --retries 1",
+                    config_path,
+                    source.display(),
+                    dest.display(),
+                    tar
+                ))
+                .is_ok()
+            );
+            run_json(&["backup", "-c", &config_path])
+        };
+
+        // The copy is kept, but flagged
+        let events = backup(false);
+        let summary = events.iter().find(|e| e["event"] == "summary").unwrap();
+        let inconsistent = summary["inconsistent"].as_array().unwrap();
+        assert_eq!(inconsistent.len(), 1);
+        assert_eq!(inconsistent[0], busy.to_str().unwrap());
+        assert_eq!(summary["failures"].as_array().unwrap().len(), 0);
+        let copied: Vec<_> = events.iter().filter(|e| e["event"] == "file_copied").collect();
+        assert_eq!(copied.len(), 2);
+        for event in copied {
+            assert_eq!(event["inconsistent"], event["source"] == busy.to_str().unwrap());
+        }
+        assert_eq!(fs::metadata(dest.join("busy.log")).unwrap().len(), 32 << 20);
+
+        // Archives stay readable, and hold what the manifest describes
+        let events = backup(true);
+        let summary = events.iter().find(|e| e["event"] == "summary").unwrap();
+        assert_eq!(summary["inconsistent"].as_array().unwrap().len(), 1);
+        stop.store(true, Ordering::Relaxed);
+        writer.join().unwrap();
+        let verified = run_json(&["verify", "-c", &config_path]);
+        let summary = verified.iter().find(|e| e["event"] == "verify_summary").unwrap();
+        assert_eq!(summary["corrupted"], 0);
+        assert_eq!(summary["missing"], 0);
+
+        let _ = fs::remove_dir_all(&source);
+        let _ = fs::remove_dir_all(&dest);
+    });
+}
+
+#[test]
+fn test_unreadable_entries() {
+    use std::os::unix::fs::PermissionsExt;
+
+    with_clean_config("test_unreadable", |config_path| {
+        let source = PathBuf::from("tests/example/unreadable_src");
+        let dest = PathBuf::from("tests/example/unreadable_dest");
+        let _ = fs::remove_dir_all(&source);
+        let _ = fs::remove_dir_all(&dest);
+        fs::create_dir_all(source.join("locked_dir")).unwrap();
+        fs::write(source.join("locked_dir/hidden.txt"), "hidden").unwrap();
+        fs::write(source.join("locked.txt"), "locked").unwrap();
+        fs::write(source.join("readable.txt"), "readable").unwrap();
+        for locked in ["locked_dir", "locked.txt"] {
+            fs::set_permissions(source.join(locked), fs::Permissions::from_mode(0o000)).unwrap();
+        }
+
+        // Run without the capabilities that let root read anything
+        let backup = |tar: bool| {
+            let _ = fs::remove_dir_all(&dest);
+            assert!(
+                repl::execute_line(format!(
+                    "config -c {} --source-path {} --dest-path {} --tar {}",
+                    config_path,
+                    source.display(),
+                    dest.display(),
+                    tar
+                ))
+                .is_ok()
+            );
+            std::process::Command::new("setpriv")
+                .arg("--bounding-set=-dac_override,-dac_read_search")
+                .arg(env!("CARGO_BIN_EXE_babycancer"))
+                .args(["backup", "-c", &config_path, "--format", "json"])
+                .output()
+                .ok()
+        };
+        let failures = |stdout: &[u8]| -> Vec<String> {
+            let summary: serde_json::Value = String::from_utf8_lossy(stdout)
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .find(|e: &serde_json::Value| e["event"] == "summary")
+                .expect("the run should end with a summary");
+            summary["failures"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|f| f["path"].as_str().unwrap().to_string())
+                .collect()
+        };
+
+        if let Some(output) = backup(false) {
+            assert_eq!(output.status.code(), Some(error::EXIT_PARTIAL));
+            assert_eq!(
+                failures(&output.stdout),
+                [
+                    source.join("locked_dir").to_str().unwrap(),
+                    source.join("locked.txt").to_str().unwrap()
+                ]
+            );
+            assert!(dest.join("readable.txt").exists());
+            assert!(!dest.join("locked_dir/hidden.txt").exists());
+        }
+
+        fs::set_permissions(source.join("locked_dir"), fs::Permissions::from_mode(0o755)).unwrap();
+        if let Some(output) = backup(true) {
+            assert_eq!(output.status.code(), Some(error::EXIT_PARTIAL));
+            assert_eq!(failures(&output.stdout), [source.join("locked.txt").to_str().unwrap()]);
+            let mut archive = tar::Archive::new(fs::File::open(dest.join("backup.tar")).unwrap());
+            let names: Vec<String> = archive
+                .entries()
+                .unwrap()
+                .map(|e| e.unwrap().path().unwrap().to_string_lossy().into_owned())
+                .collect();
+            assert!(names.contains(&"readable.txt".to_string()));
+            assert!(!names.contains(&"locked.txt".to_string()));
+        }
+
+        let _ = fs::remove_dir_all(&source);
+        let _ = fs::remove_dir_all(&dest);
+    });
+}
+
+#[test]
+fn test_failure_reasons() {
+    with_clean_config("test_failure_reasons", |config_path| {
+        let source = PathBuf::from("tests/example/failure_reasons_src");
+        let dest = PathBuf::from("tests/example/failure_reasons_dest");
+        let _ = fs::remove_dir_all(&source);
+        let _ = fs::remove_dir_all(&dest);
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("blocked.txt"), "blocked").unwrap();
+        // A directory in the way of the copy, that cannot be removed without
+        // the capabilities that let root write anywhere
+        fs::create_dir_all(dest.join("blocked.txt/inner")).unwrap();
+        fs::write(dest.join("blocked.txt/inner/kept"), "kept").unwrap();
+        let read_only = std::os::unix::fs::PermissionsExt::from_mode(0o555);
+        fs::set_permissions(dest.join("blocked.txt/inner"), read_only).unwrap();
+
+        assert!(
+            repl::execute_line(format!(
+                "config -c {} --source-path {} --dest-path {}",
+                config_path,
+                source.display(),
+                dest.display()
+            ))
+            .is_ok()
+        );
+        let Ok(output) = std::process::Command::new("setpriv")
+            .arg("--bounding-set=-dac_override")
+            .arg(env!("CARGO_BIN_EXE_babycancer"))
+            .args(["backup", "-c", &config_path, "--format", "json"])
+            .output()
+        else {
+            return;
+        };
+        assert_eq!(output.status.code(), Some(error::EXIT_PARTIAL));
+        let events: Vec<serde_json::Value> = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        // The failure names the destination, which is what failed, once
+        let blocked = dest.join("blocked.txt");
+        let error = events.iter().find(|e| e["event"] == "error").unwrap();
+        assert_eq!(error["path"], blocked.to_str().unwrap());
+        let message = error["message"].as_str().unwrap();
+        assert!(!message.contains("blocked.txt"), "{}", message);
+
+        let summary = events.iter().find(|e| e["event"] == "summary").unwrap();
+        let failures = summary["failures"].as_array().unwrap();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0]["path"], blocked.to_str().unwrap());
+        assert_eq!(failures[0]["reason"], message);
+
+        let _ = fs::remove_dir_all(&source);
+        let _ = fs::remove_dir_all(&dest);
+    });
+}
+
+#[test]
+fn test_long_file_names() {
+    with_clean_config("test_long_names", |config_path| {
+        let source = PathBuf::from("tests/example/long_names_src");
+        let dest = PathBuf::from("tests/example/long_names_dest");
+        let _ = fs::remove_dir_all(&source);
+        let _ = fs::remove_dir_all(&dest);
+        fs::create_dir_all(&source).unwrap();
+        // Close to the 255 bytes most file systems allow
+        let name = format!("{}.txt", "n".repeat(246));
+        assert_eq!(name.len(), 250);
+        fs::write(source.join(&name), "long").unwrap();
+
+        assert!(
+            repl::execute_line(format!(
+                "config -c {} --source-path {} --dest-path {}",
+                config_path,
+                source.display(),
+                dest.display()
+            ))
+            .is_ok()
+        );
+        assert!(repl::execute_line(format!("backup -c {}", config_path)).is_ok());
+        assert_eq!(fs::read(dest.join(&name)).unwrap(), b"long");
+        assert!(repl::execute_line(format!("verify -c {}", config_path)).is_ok());
+
+        let _ = fs::remove_dir_all(&source);
+        let _ = fs::remove_dir_all(&dest);
+    });
+}
+
+#[test]
+fn test_resume_same_second() {
+    with_clean_config("test_resume_same_second", |config_path| {
+        let source = PathBuf::from("tests/example/resume_second_src");
+        let dest = PathBuf::from("tests/example/resume_second_dest");
+        let _ = fs::remove_dir_all(&source);
+        let _ = fs::remove_dir_all(&dest);
+        fs::create_dir_all(&source).unwrap();
+        let file = source.join("data.txt");
+        let set_mtime = |nanos| {
+            let mtime = std::time::UNIX_EPOCH + std::time::Duration::new(1_700_000_000, nanos);
+            let times = fs::FileTimes::new().set_modified(mtime);
+            fs::File::options().write(true).open(&file).unwrap().set_times(times).unwrap();
+        };
+        fs::write(&file, "first").unwrap();
+        set_mtime(100);
+
+        assert!(
+            repl::execute_line(format!(
+                "config -c {} --source-path {} --dest-path {}",
+                config_path,
+                source.display(),
+                dest.display()
+            ))
+            .is_ok()
+        );
+        assert!(repl::execute_line(format!("backup -c {}", config_path)).is_ok());
+
+        // Interrupt the run after data.txt, then rewrite it within the same
+        // second and at the same size
+        let manifest = manifest::Manifest::load(&dest).unwrap();
+        let journal: String = manifest
+            .entries
+            .iter()
+            .map(|entry| serde_json::to_string(entry).unwrap() + "\n")
+            .collect();
+        let header = serde_json::json!({ "algorithm": manifest.algorithm }).to_string();
+        fs::write(dest.join(journal::JOURNAL_FILE_NAME), header + "\n" + &journal).unwrap();
+        fs::write(&file, "other").unwrap();
+        set_mtime(200);
+
+        let events = run_json(&["backup", "-c", &config_path, "--resume"]);
+        let summary = events.iter().find(|e| e["event"] == "summary").unwrap();
+        assert_eq!(summary["files_resumed"], 0);
+        assert_eq!(summary["files_copied"], 1);
+        assert_eq!(fs::read_to_string(dest.join("data.txt")).unwrap(), "other");
+
+        let _ = fs::remove_dir_all(&source);
+        let _ = fs::remove_dir_all(&dest);
+    });
+}
+
+#[test]
+fn test_fifo_replaces_dangling_symlink() {
+    use std::os::unix::fs::FileTypeExt;
+
+    with_clean_config("test_fifo_symlink", |config_path| {
+        let source = PathBuf::from("tests/example/fifo_symlink_src");
+        let dest = PathBuf::from("tests/example/fifo_symlink_dest");
+        let _ = fs::remove_dir_all(&source);
+        let _ = fs::remove_dir_all(&dest);
+        fs::create_dir_all(&source).unwrap();
+        fs::create_dir_all(&dest).unwrap();
+        nix::unistd::mkfifo(&source.join("pipe"), nix::sys::stat::Mode::S_IRWXU).unwrap();
+        // What the last backup left where the FIFO now is
+        std::os::unix::fs::symlink("missing", dest.join("pipe")).unwrap();
+
+        assert!(
+            repl::execute_line(format!(
+                "config -c {} --source-path {} --dest-path {}",
+                config_path,
+                source.display(),
+                dest.display()
+            ))
+            .is_ok()
+        );
+        assert!(repl::execute_line(format!("backup -c {}", config_path)).is_ok());
+        assert!(fs::symlink_metadata(dest.join("pipe")).unwrap().file_type().is_fifo());
+
+        let _ = fs::remove_dir_all(&source);
+        let _ = fs::remove_dir_all(&dest);
+    });
+}
+
+#[test]
+fn test_type_changes() {
+    with_clean_config("test_type_changes", |config_path| {
+        let source = PathBuf::from("tests/example/type_changes_src");
+        let dest = PathBuf::from("tests/example/type_changes_dest");
+        let _ = fs::remove_dir_all(&source);
+        let _ = fs::remove_dir_all(&dest);
+        fs::create_dir_all(source.join("was_dir")).unwrap();
+        fs::write(source.join("was_dir/inner.txt"), "inner").unwrap();
+        fs::write(source.join("was_file"), "file").unwrap();
+        fs::write(source.join("was_empty_file"), "file").unwrap();
+        fs::write(source.join("other.txt"), "other").unwrap();
+
+        assert!(
+            repl::execute_line(format!(
+                "config -c {} --source-path {} --dest-path {} --keep-empty-dirs true",
+                config_path,
+                source.display(),
+                dest.display()
+            ))
+            .is_ok()
+        );
+        assert!(repl::execute_line(format!("backup -c {}", config_path)).is_ok());
+
+        // Every path changes type, and earlier copies are in the way
+        fs::remove_dir_all(source.join("was_dir")).unwrap();
+        std::os::unix::fs::symlink("other.txt", source.join("was_dir")).unwrap();
+        fs::remove_file(source.join("was_file")).unwrap();
+        fs::create_dir_all(source.join("was_file")).unwrap();
+        fs::write(source.join("was_file/bar.txt"), "bar").unwrap();
+        fs::remove_file(source.join("was_empty_file")).unwrap();
+        fs::create_dir_all(source.join("was_empty_file")).unwrap();
+
+        assert!(repl::execute_line(format!("backup -c {}", config_path)).is_ok());
+        let file_type = |name: &str| fs::symlink_metadata(dest.join(name)).unwrap().file_type();
+        assert!(file_type("was_dir").is_symlink());
+        assert!(file_type("was_file").is_dir());
+        assert_eq!(fs::read_to_string(dest.join("was_file/bar.txt")).unwrap(), "bar");
+        assert!(file_type("was_empty_file").is_dir());
+        assert!(!dest.join(journal::JOURNAL_FILE_NAME).exists());
+        assert!(repl::execute_line(format!("verify -c {}", config_path)).is_ok());
+
+        let _ = fs::remove_dir_all(&source);
+        let _ = fs::remove_dir_all(&dest);
+    });
+}
+
+#[test]
+fn test_archive_events() {
+    with_clean_config("test_archive_events", |config_path| {
+        let source = PathBuf::from("tests/example/archive_events_src");
+        let dest = PathBuf::from("tests/example/archive_events_dest");
+        let _ = fs::remove_dir_all(&source);
+        let _ = fs::remove_dir_all(&dest);
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("file.txt"), "file").unwrap();
+        fs::hard_link(source.join("file.txt"), source.join("link.txt")).unwrap();
+        std::os::unix::fs::symlink("file.txt", source.join("symlink")).unwrap();
+
+        assert!(
+            repl::execute_line(format!(
+                "config -c {} --source-path {} --dest-path {} --tar true",
+                config_path,
+                source.display(),
+                dest.display()
+            ))
+            .is_ok()
+        );
+        let events = run_json(&["backup", "-c", &config_path]);
+
+        // The same per-entry events as for plain copies
+        let find = |event: &str| events.iter().find(|e| e["event"] == event).unwrap();
+        // Whichever of the two links is walked first holds the data
+        let copied = find("file_copied");
+        let name = copied["dest"].as_str().unwrap();
+        assert!(name == "file.txt" || name == "link.txt");
+        assert_eq!(copied["source"], source.join(name).to_str().unwrap());
+        assert_eq!(copied["bytes"], 4);
+        let manifest = manifest::Manifest::load(&dest).unwrap();
+        let entry = manifest.entries.iter().find(|e| e.path == Path::new(name)).unwrap();
+        assert_eq!(copied["hash"], entry.hash.as_deref().unwrap());
+        assert_eq!(find("hard_linked")["target"], name);
+        assert_eq!(find("special_copied")["kind"], "symlink");
+        assert!(events.iter().any(|e| e["event"] == "archive_created"));
+
+        let _ = fs::remove_dir_all(&source);
+        let _ = fs::remove_dir_all(&dest);
+    });
+}
+
+#[test]
+fn test_archive_special_files() {
+    use nix::sys::stat::{Mode, SFlag, makedev, mknod};
+
+    with_clean_config("test_archive_special", |config_path| {
+        let source = std::path::absolute("tests/example/archive_special_src").unwrap();
+        let dest = PathBuf::from("tests/example/archive_special_dest");
+        let _ = fs::remove_dir_all(&source);
+        let _ = fs::remove_dir_all(&dest);
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("file.txt"), "file").unwrap();
+        nix::unistd::mkfifo(&source.join("pipe"), Mode::S_IRWXU).unwrap();
+        let device = source.join("node");
+        let has_device = mknod(&device, SFlag::S_IFCHR, Mode::S_IRUSR, makedev(1, 3)).is_ok();
+
+        assert!(
+            repl::execute_line(format!(
+                "config -c {} --source-path {} --dest-path {} --tar true",
+                config_path,
+                source.display(),
+                dest.display()
+            ))
+            .is_ok()
+        );
+        assert!(repl::execute_line(format!("backup -c {}", config_path)).is_ok());
+
+        // Stored under their name relative to the source, like everything else
+        let mut archive = tar::Archive::new(fs::File::open(dest.join("backup.tar")).unwrap());
+        let mut entries = std::collections::HashMap::new();
+        for entry in archive.entries().unwrap() {
+            let entry = entry.unwrap();
+            let header = entry.header();
+            let device = (header.device_major().unwrap(), header.device_minor().unwrap());
+            let name = entry.path().unwrap().to_string_lossy().into_owned();
+            entries.insert(name, (header.entry_type(), device));
+        }
+        assert_eq!(entries["pipe"].0, tar::EntryType::Fifo);
+        if has_device {
+            assert_eq!(entries["node"], (tar::EntryType::Char, (Some(1), Some(3))));
+        }
+        assert!(repl::execute_line(format!("verify -c {}", config_path)).is_ok());
+
+        let _ = fs::remove_dir_all(&source);
+        let _ = fs::remove_dir_all(&dest);
+    });
+}
+
+#[test]
+fn test_diff_same_second() {
+    with_clean_config("test_diff_same_second", |config_path| {
+        let source = PathBuf::from("tests/example/diff_second_src");
+        let dest = PathBuf::from("tests/example/diff_second_dest");
+        let _ = fs::remove_dir_all(&source);
+        let _ = fs::remove_dir_all(&dest);
+        fs::create_dir_all(&source).unwrap();
+        let file = source.join("data.txt");
+        let set_mtime = |nanos| {
+            let mtime = std::time::UNIX_EPOCH + std::time::Duration::new(1_700_000_000, nanos);
+            let times = fs::FileTimes::new().set_modified(mtime);
+            fs::File::options().write(true).open(&file).unwrap().set_times(times).unwrap();
+        };
+        fs::write(&file, "first").unwrap();
+        set_mtime(100_000_000);
+
+        assert!(
+            repl::execute_line(format!(
+                "config -c {} --source-path {} --dest-path {}",
+                config_path,
+                source.display(),
+                dest.display()
+            ))
+            .is_ok()
+        );
+        assert!(repl::execute_line(format!("backup -c {}", config_path)).is_ok());
+
+        // Rewritten within the same second, at the same size
+        fs::write(&file, "other").unwrap();
+        set_mtime(900_000_000);
+
+        let events = run_json(&["diff", "-c", &config_path]);
+        let summary = events.iter().find(|e| e["event"] == "diff_summary").unwrap();
+        assert_eq!(summary["modified"], 1);
+        assert_eq!(summary["unchanged"], 0);
+
+        let _ = fs::remove_dir_all(&source);
+        let _ = fs::remove_dir_all(&dest);
+    });
+}
+
+#[test]
+fn test_diff_unreadable() {
+    use std::os::unix::fs::PermissionsExt;
+
+    with_clean_config("test_diff_unreadable", |config_path| {
+        let source = PathBuf::from("tests/example/diff_unreadable_src");
+        let dest = PathBuf::from("tests/example/diff_unreadable_dest");
+        let _ = fs::remove_dir_all(&source);
+        let _ = fs::remove_dir_all(&dest);
+        fs::create_dir_all(source.join("locked_dir")).unwrap();
+        fs::write(source.join("locked_dir/hidden.txt"), "hidden").unwrap();
+        fs::write(source.join("readable.txt"), "readable").unwrap();
+
+        assert!(
+            repl::execute_line(format!(
+                "config -c {} --source-path {} --dest-path {}",
+                config_path,
+                source.display(),
+                dest.display()
+            ))
+            .is_ok()
+        );
+        assert!(repl::execute_line(format!("backup -c {}", config_path)).is_ok());
+        fs::set_permissions(source.join("locked_dir"), fs::Permissions::from_mode(0o000)).unwrap();
+
+        // Run without the capabilities that let root read anything
+        let output = std::process::Command::new("setpriv")
+            .arg("--bounding-set=-dac_override,-dac_read_search")
+            .arg(env!("CARGO_BIN_EXE_babycancer"))
+            .args(["diff", "-c", &config_path, "--format", "json"])
+            .output();
+        if let Ok(output) = output {
+            assert_eq!(output.status.code(), Some(error::EXIT_PARTIAL));
+            let events: Vec<serde_json::Value> = String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect();
+            // Reported as part of the diff, not as a failed backup
+            assert!(!events.iter().any(|e| e["event"] == "error" && !e["path"].is_null()));
+            let unreadable: Vec<_> = events
+                .iter()
+                .filter(|e| e["event"] == "diff_entry" && e["change"] == "unreadable")
+                .map(|e| e["path"].as_str().unwrap())
+                .collect();
+            assert_eq!(unreadable, ["locked_dir"]);
+            let summary = events.iter().find(|e| e["event"] == "diff_summary").unwrap();
+            assert_eq!(summary["unreadable"], 1);
+            assert_eq!(summary["removed"], 0);
+        }
+
+        fs::set_permissions(source.join("locked_dir"), fs::Permissions::from_mode(0o755)).unwrap();
+        let _ = fs::remove_dir_all(&source);
+        let _ = fs::remove_dir_all(&dest);
+    });
+}